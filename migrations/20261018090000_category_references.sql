@@ -0,0 +1,23 @@
+-- drop references to categories that do not exist before enforcing them
+update category
+set parent_id = null
+where parent_id is not null
+    and parent_id not in (select ap_id from category);
+
+update category c
+set sub_categories = array(
+    select sub
+    from unnest(c.sub_categories) with ordinality as s(sub, ord)
+    where exists (select 1 from category where ap_id = s.sub)
+    order by ord
+)
+where exists (
+    select 1
+    from unnest(c.sub_categories) as s(sub)
+    where not exists (select 1 from category where ap_id = s.sub)
+);
+
+-- deleting a category deletes everything beneath it
+alter table category
+    add constraint category_parent_id_fkey
+    foreign key (parent_id) references category (ap_id) on delete cascade;
@@ -0,0 +1,14 @@
+-- rebuild sub_categories from parent_id wherever the two disagree
+update category p
+set sub_categories = children.ap_ids
+from (
+    select
+        parent.ap_id,
+        coalesce(array_agg(child.ap_id order by child.created_at, child.id)
+            filter (where child.ap_id is not null), '{}') as ap_ids
+    from category parent
+    left join category child on child.parent_id = parent.ap_id
+    group by parent.ap_id
+) as children
+where p.ap_id = children.ap_id
+    and not (p.sub_categories @> children.ap_ids and children.ap_ids @> p.sub_categories);
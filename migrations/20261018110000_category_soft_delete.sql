@@ -0,0 +1,4 @@
+-- deleted categories are kept until purged so they can be restored
+alter table category add column deleted_at timestamptz;
+
+create index idx_category_deleted_at on category (deleted_at) where deleted_at is not null;
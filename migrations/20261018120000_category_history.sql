@@ -0,0 +1,24 @@
+-- every change made to a category, with the row before and after it
+create table category_history (
+    id varchar(21) primary key,
+    category_ap_id varchar not null, -- no foreign key, history outlives purged categories
+    operation varchar not null check (operation in ('create', 'upsert', 'update', 'delete', 'restore', 'purge')),
+    actor varchar, -- taken from request metadata, if provided
+    before jsonb, -- null when the category was created
+    after jsonb, -- null when the category was purged
+    created_at timestamptz default clock_timestamp() not null
+);
+
+create index idx_category_history_category_created on category_history (category_ap_id, created_at, id);
+
+create or replace function reject_category_history_change()
+returns trigger as $$
+begin
+    raise exception 'category_history is append-only';
+end;
+$$ language plpgsql;
+
+create trigger category_history_append_only
+before update or delete on category_history
+for each row
+execute function reject_category_history_change();
@@ -0,0 +1,8 @@
+-- category change events waiting to be published by the outbox relay
+create table category_outbox (
+    id bigint generated always as identity primary key,
+    event varchar not null check (event in ('CategoryCreated', 'CategoryUpdated', 'CategoryDeleted')),
+    category_ap_id varchar not null,
+    payload jsonb not null, -- the category as it was after the change
+    created_at timestamptz default clock_timestamp() not null
+);
@@ -0,0 +1,47 @@
+-- announce category changes to the watch rpc, once the change commits
+create or replace function notify_category_change()
+returns trigger as $$
+declare
+    kind text;
+    category category;
+    previous_parent_id varchar;
+begin
+    if tg_op = 'INSERT' then
+        kind = 'created';
+        category = new;
+    elsif tg_op = 'DELETE' then
+        -- purged categories were announced when they were deleted
+        if old.deleted_at is not null then
+            return null;
+        end if;
+        kind = 'deleted';
+        category = old;
+    elsif old.deleted_at is null and new.deleted_at is not null then
+        kind = 'deleted';
+        category = new;
+    elsif old.deleted_at is not null and new.deleted_at is null then
+        kind = 'created';
+        category = new;
+    elsif new.deleted_at is null then
+        kind = 'updated';
+        category = new;
+        previous_parent_id = old.parent_id;
+    else
+        return null;
+    end if;
+
+    perform pg_notify('category_changes', json_build_object(
+        'kind', kind,
+        'ap_id', category.ap_id,
+        'parent_id', category.parent_id,
+        'previous_parent_id', previous_parent_id
+    )::text);
+
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger notify_category_change
+after insert or update or delete on category
+for each row
+execute function notify_category_change();
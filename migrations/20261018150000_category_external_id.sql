@@ -0,0 +1,11 @@
+-- identifiers of categories in external taxonomies, such as google's product taxonomy
+create table category_external_id (
+    taxonomy varchar not null,
+    external_id varchar not null,
+    category_ap_id varchar not null references category (ap_id) on delete cascade,
+    created_at timestamptz default now() not null,
+    primary key (taxonomy, external_id)
+);
+
+-- a category has at most one identifier in each taxonomy
+create unique index idx_category_external_id_category on category_external_id (category_ap_id, taxonomy);
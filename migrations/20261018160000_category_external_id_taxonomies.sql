@@ -0,0 +1,3 @@
+-- the external taxonomies categories can be mapped to
+alter table category_external_id
+    add constraint category_external_id_taxonomy_check check (taxonomy in ('google', 'ebay', 'amazon'));
@@ -0,0 +1,8 @@
+-- full-text and fuzzy matching of category names for search
+create extension if not exists pg_trgm;
+
+create index idx_category_name_fts on category using gin (to_tsvector('english', name)) where deleted_at is null;
+create index idx_category_name_trgm on category using gin (name gin_trgm_ops) where deleted_at is null;
+
+-- no query filters on an exact name
+drop index if exists idx_category_name;
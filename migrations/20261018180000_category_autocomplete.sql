@@ -0,0 +1,73 @@
+-- how deep each category sits and how often it is picked, for ranking autocomplete
+-- suggestions. kept apart from category so that neither bumps updated_at
+create table category_rank (
+    category_ap_id varchar primary key references category (ap_id) on delete cascade,
+    depth integer not null default 0,
+    popularity bigint not null default 0
+);
+
+-- suggestions match the start of a name, or the start of any word in it
+create index idx_category_name_prefix on category (lower(name) text_pattern_ops) where deleted_at is null;
+create index idx_category_name_words on category using gin (to_tsvector('simple', name)) where deleted_at is null;
+
+-- recomputes the depth of the given categories and everything beneath them
+create or replace function sync_category_depth(ap_ids varchar[])
+returns void as $$
+begin
+    with recursive up (ap_id, parent_id, depth) as (
+        select ap_id, parent_id, 0 from category where ap_id = any(ap_ids)
+        union all
+        select up.ap_id, p.parent_id, up.depth + 1
+        from up
+        join category p on p.ap_id = up.parent_id
+        where up.depth < 100
+    ),
+    own as (
+        select ap_id, max(depth) as depth from up group by ap_id
+    ),
+    down (ap_id, depth) as (
+        select ap_id, depth from own
+        union all
+        select c.ap_id, down.depth + 1
+        from category c
+        join down on c.parent_id = down.ap_id
+        where down.depth < 100
+    )
+    insert into category_rank (category_ap_id, depth)
+    select distinct on (ap_id) ap_id, depth from down
+    on conflict (category_ap_id) do update
+    set depth = excluded.depth
+    where category_rank.depth <> excluded.depth;
+end;
+$$ language plpgsql;
+
+create or replace function sync_category_rank()
+returns trigger as $$
+begin
+    if tg_op = 'INSERT' then
+        perform sync_category_depth(array(select ap_id from new_rows));
+    else
+        perform sync_category_depth(array(
+            select n.ap_id
+            from new_rows n
+            join old_rows o on o.ap_id = n.ap_id
+            where n.parent_id is distinct from o.parent_id
+        ));
+    end if;
+    return null;
+end;
+$$ language plpgsql;
+
+create trigger sync_category_rank_insert
+after insert on category
+referencing new table as new_rows
+for each statement
+execute function sync_category_rank();
+
+create trigger sync_category_rank_update
+after update on category
+referencing old table as old_rows new table as new_rows
+for each statement
+execute function sync_category_rank();
+
+select sync_category_depth(array(select ap_id from category where parent_id is null));
@@ -0,0 +1,10 @@
+-- the host an ap_id lives on, which for remote categories is the instance they came from
+create or replace function category_host(ap_id varchar)
+returns varchar as $$
+    select lower(substring(ap_id from '^[a-zA-Z][a-zA-Z0-9+.-]*://(?:[^/?#@]*@)?([^/?#:]+)'));
+$$ language sql immutable strict parallel safe;
+
+-- listing remote categories, all of them, or those from one host
+create index idx_category_remote_created on category (created_at, id) where not local and deleted_at is null;
+create index idx_category_created on category (created_at, id) where deleted_at is null;
+create index idx_category_host_created on category (category_host(ap_id), created_at, id) where deleted_at is null;
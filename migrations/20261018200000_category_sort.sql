@@ -0,0 +1,3 @@
+-- categories can also be listed by name or by when they last changed
+create index idx_category_name_id on category (name, id) where deleted_at is null;
+create index idx_category_updated on category (updated_at, id) where deleted_at is null;
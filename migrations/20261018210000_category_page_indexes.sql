@@ -0,0 +1,7 @@
+-- pages of local or remote categories, and of a category's sub-categories,
+-- read straight off these in order
+create index idx_category_local_created on category (local, created_at, id) where deleted_at is null;
+create index idx_category_parent_local_created on category (parent_id, local, created_at, id) where deleted_at is null;
+
+-- covered by idx_category_local_created
+drop index if exists idx_category_remote_created;
@@ -0,0 +1,5 @@
+-- categories the seed file has declared, so only those are deleted once the
+-- file stops declaring them
+create table seeded_category (
+    ap_id varchar primary key -- no foreign key, categories may be purged
+);
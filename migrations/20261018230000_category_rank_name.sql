@@ -0,0 +1,38 @@
+-- a copy of each category's name, so that autocomplete can walk suggestions in
+-- rank order off one index and stop once it has enough, rather than sorting
+-- every category matching a short prefix
+alter table category_rank add column name varchar;
+
+update category_rank r set name = c.name
+from category c
+where c.ap_id = r.category_ap_id;
+
+create index idx_category_rank_order on category_rank (depth, popularity desc, name, category_ap_id);
+create index idx_category_rank_prefix on category_rank (lower(name) text_pattern_ops);
+create index idx_category_rank_words on category_rank using gin (to_tsvector('simple', name));
+
+-- autocomplete matches names through category_rank now
+drop index if exists idx_category_name_prefix;
+drop index if exists idx_category_name_words;
+
+create or replace function sync_category_rank()
+returns trigger as $$
+begin
+    if tg_op = 'INSERT' then
+        perform sync_category_depth(array(select ap_id from new_rows));
+    else
+        perform sync_category_depth(array(
+            select n.ap_id
+            from new_rows n
+            join old_rows o on o.ap_id = n.ap_id
+            where n.parent_id is distinct from o.parent_id
+        ));
+    end if;
+
+    update category_rank r set name = n.name
+    from new_rows n
+    where r.category_ap_id = n.ap_id and r.name is distinct from n.name;
+
+    return null;
+end;
+$$ language plpgsql;
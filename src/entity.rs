@@ -1,17 +1,26 @@
+use sellershut_core::categories::{CategoryHistoryEntry, ExternalId, ExternalTaxonomy};
 use serde::Deserialize;
+use serde_json::Value;
 use time::OffsetDateTime;
 
-#[derive(Debug, Deserialize, Clone)]
+use crate::google;
+
+/// Also deserialized from the `to_jsonb` snapshots kept in the category history
+#[derive(Debug, Deserialize, Clone, sqlx::FromRow)]
 pub struct Category {
     pub id: String,
     pub name: String,
     pub sub_categories: Vec<String>,
     pub image_url: Option<String>,
     pub parent_id: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
     pub updated_at: OffsetDateTime,
     pub ap_id: String,
     pub local: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub deleted_at: Option<OffsetDateTime>,
 }
 
 impl From<Category> for sellershut_core::categories::Category {
@@ -43,3 +52,119 @@ pub struct CategoryDetailed {
     pub ap_id: String,
     pub local: bool,
 }
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CategoryDescendant {
+    pub id: String,
+    pub name: String,
+    pub depth: i32,
+    pub sub_category_name: Option<String>,
+    pub sub_category_ap_id: Option<String>,
+    pub image_url: Option<String>,
+    pub parent_id: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    pub ap_id: String,
+    pub local: bool,
+}
+
+/// A category found by a search, with how relevant it is to the search term
+#[derive(Debug, Clone)]
+pub struct CategoryMatch {
+    pub id: String,
+    pub name: String,
+    pub sub_categories: Vec<String>,
+    pub image_url: Option<String>,
+    pub parent_id: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+    pub ap_id: String,
+    pub local: bool,
+    pub rank: f32,
+}
+
+impl From<CategoryMatch> for sellershut_core::categories::Category {
+    fn from(value: CategoryMatch) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            sub_categories: value.sub_categories,
+            image_url: value.image_url,
+            parent_id: value.parent_id,
+            created_at: Some(value.created_at.into()),
+            updated_at: Some(value.updated_at.into()),
+            ap_id: value.ap_id,
+            local: value.local,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CategoryHistory {
+    pub id: String,
+    pub category_ap_id: String,
+    pub operation: String,
+    pub actor: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: OffsetDateTime,
+}
+
+impl TryFrom<CategoryHistory> for CategoryHistoryEntry {
+    type Error = serde_json::Error;
+
+    fn try_from(value: CategoryHistory) -> Result<Self, Self::Error> {
+        let snapshot = |value: Option<Value>| {
+            value
+                .map(serde_json::from_value::<Category>)
+                .transpose()
+                .map(|category| category.map(Into::into))
+        };
+
+        Ok(Self {
+            id: value.id,
+            ap_id: value.category_ap_id,
+            operation: value.operation,
+            actor: value.actor,
+            before: snapshot(value.before)?,
+            after: snapshot(value.after)?,
+            created_at: Some(value.created_at.into()),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CategoryExternalId {
+    pub taxonomy: String,
+    pub external_id: String,
+}
+
+impl From<CategoryExternalId> for ExternalId {
+    fn from(value: CategoryExternalId) -> Self {
+        let taxonomy = taxonomy_from_name(&value.taxonomy).unwrap_or_default();
+        Self {
+            taxonomy: taxonomy.into(),
+            external_id: value.external_id,
+        }
+    }
+}
+
+/// How an external taxonomy is named in `category_external_id`
+pub fn taxonomy_name(taxonomy: ExternalTaxonomy) -> Option<&'static str> {
+    match taxonomy {
+        ExternalTaxonomy::Unspecified => None,
+        ExternalTaxonomy::Google => Some(google::TAXONOMY),
+        ExternalTaxonomy::Ebay => Some("ebay"),
+        ExternalTaxonomy::Amazon => Some("amazon"),
+    }
+}
+
+pub fn taxonomy_from_name(name: &str) -> Option<ExternalTaxonomy> {
+    [
+        ExternalTaxonomy::Google,
+        ExternalTaxonomy::Ebay,
+        ExternalTaxonomy::Amazon,
+    ]
+    .into_iter()
+    .find(|taxonomy| taxonomy_name(*taxonomy) == Some(name))
+}
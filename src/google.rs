@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, bail};
+use sellershut_core::categories::Category;
+use tracing::{Instrument, debug_span, info};
+use url::Url;
+
+use crate::{
+    seed::{self, SeedSummary},
+    state::AppState,
+    transfer,
+};
+
+/// The taxonomy Google product category ids are mapped under
+pub const TAXONOMY: &str = "google";
+
+/// Recorded as the actor in the history of imported categories
+const GOOGLE_ACTOR: &str = "google-taxonomy";
+
+/// A line of Google's `taxonomy-with-ids` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoogleCategory {
+    pub id: String,
+    /// Names from the top-level category down to this one
+    pub path: Vec<String>,
+}
+
+/// Parses lines like `3237 - Animals & Pet Supplies > Live Animals`, skipping
+/// blank lines and the `#` version header
+pub fn parse(input: &str) -> anyhow::Result<Vec<GoogleCategory>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            let (id, path) = line
+                .split_once(" - ")
+                .with_context(|| format!("line {}: expected `<id> - <path>`", i + 1))?;
+
+            let id = id.trim();
+            if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+                bail!("line {}: invalid id {id:?}", i + 1);
+            }
+
+            let path: Vec<_> = path
+                .split(" > ")
+                .map(|name| name.trim().to_string())
+                .collect();
+            if path.iter().any(String::is_empty) {
+                bail!("line {}: empty category name", i + 1);
+            }
+
+            Ok(GoogleCategory {
+                id: id.to_string(),
+                path,
+            })
+        })
+        .collect()
+}
+
+/// Builds the category tree described by a Google taxonomy file and maps each
+/// category to its Google id. New categories get an `ap_id` beneath `base_url`,
+/// while ids that are already mapped keep their category, so importing a newer
+/// version renames and moves categories in place. Categories Google has
+/// dropped are left alone. The categories and their ids are written together,
+/// or not at all
+pub async fn import(state: &AppState, input: &str, base_url: &Url) -> anyhow::Result<SeedSummary> {
+    let google = parse(input)?;
+
+    let mut tx = state.services.postgres.begin().await?;
+
+    let mut ap_ids: HashMap<_, _> = sqlx::query!(
+        "select external_id, category_ap_id from category_external_id where taxonomy = $1",
+        TAXONOMY
+    )
+    .fetch_all(&mut *tx)
+    .instrument(debug_span!("pg.select.external_id"))
+    .await?
+    .into_iter()
+    .map(|row| (row.external_id, row.category_ap_id))
+    .collect();
+
+    let base = base_url.as_str().trim_end_matches('/');
+    for category in google.iter() {
+        ap_ids
+            .entry(category.id.clone())
+            .or_insert_with(|| format!("{base}/google-{}", category.id));
+    }
+
+    let by_path: HashMap<_, _> = google
+        .iter()
+        .map(|category| (category.path.as_slice(), category.id.as_str()))
+        .collect();
+
+    let declared = google
+        .iter()
+        .map(|category| {
+            let (name, parent_path) = category.path.split_last().expect("path to not be empty");
+            let parent_id = match parent_path {
+                [] => None,
+                parent_path => {
+                    let parent = by_path.get(parent_path).with_context(|| {
+                        format!(
+                            "{}: parent category {:?} is not in the taxonomy",
+                            category.id,
+                            parent_path.join(" > ")
+                        )
+                    })?;
+                    Some(ap_ids[*parent].clone())
+                }
+            };
+
+            Ok(Category {
+                name: name.clone(),
+                ap_id: ap_ids[&category.id].clone(),
+                parent_id,
+                local: true,
+                ..Default::default()
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let summary = seed::apply(&mut tx, &transfer::parents_first(declared), GOOGLE_ACTOR).await?;
+
+    let (external_ids, category_ap_ids): (Vec<_>, Vec<_>) = google
+        .iter()
+        .map(|category| (category.id.clone(), ap_ids[&category.id].clone()))
+        .unzip();
+
+    // a category holds one google id, so one taken over by another id loses the
+    // id Google no longer lists
+    sqlx::query!(
+        "
+        delete from category_external_id
+        where taxonomy = $1 and category_ap_id = any($3) and not external_id = any($2)",
+        TAXONOMY,
+        &external_ids,
+        &category_ap_ids
+    )
+    .execute(&mut *tx)
+    .instrument(debug_span!("pg.delete.external_id"))
+    .await?;
+
+    sqlx::query!(
+        "
+        insert into category_external_id (taxonomy, external_id, category_ap_id)
+        select $1, v.external_id, v.category_ap_id
+        from unnest($2::varchar[], $3::varchar[]) as v (external_id, category_ap_id)
+        on conflict (taxonomy, external_id)
+        do update set category_ap_id = excluded.category_ap_id",
+        TAXONOMY,
+        &external_ids,
+        &category_ap_ids
+    )
+    .execute(&mut *tx)
+    .instrument(debug_span!("pg.insert.external_id"))
+    .await?;
+
+    tx.commit().await?;
+
+    info!(
+        categories = google.len(),
+        inserted = summary.inserted,
+        updated = summary.updated,
+        skipped = summary.skipped,
+        "imported google taxonomy"
+    );
+
+    Ok(summary)
+}
@@ -1,23 +1,59 @@
 pub mod entity;
+pub mod google;
+pub mod outbox;
+pub mod seed;
 pub mod server;
 pub mod state;
+pub mod transfer;
 pub mod utils;
+pub mod watch;
 
+use std::time::Duration;
+
+use seed::SeedConfig;
 use serde::Deserialize;
+use sqlx::PgPool;
 use state::AppState;
 use tracing::{debug, trace};
 
-#[derive(Deserialize, Clone, Copy)]
+#[derive(Deserialize, Clone)]
 pub struct AppConfig {
     pub max_query_results: i32,
+    /// Days a deleted category can still be restored before `purge` removes it
+    pub deleted_retention_days: i32,
+    /// Milliseconds between checks of the outbox for category events to publish
+    pub outbox_interval_ms: u64,
+    /// A category tree to reconcile into the database on startup
+    #[serde(default)]
+    pub seed: Option<SeedConfig>,
 }
 
-pub async fn run(state: AppState, tx: tokio::sync::oneshot::Sender<u16>) -> anyhow::Result<()> {
+pub async fn migrate(pool: &PgPool) -> anyhow::Result<()> {
     trace!("running migrations");
-    sqlx::migrate!("./migrations")
-        .run(&state.services.postgres)
-        .await?;
+    sqlx::migrate!("./migrations").run(pool).await?;
     debug!("ran migrations");
+    Ok(())
+}
+
+pub async fn run(state: AppState, tx: tokio::sync::oneshot::Sender<u16>) -> anyhow::Result<()> {
+    migrate(&state.services.postgres).await?;
+
+    if let Some(ref seed) = state.config.seed {
+        seed::reconcile(&state, seed).await?;
+    }
+
+    let listener = watch::listen(&state.services.postgres).await?;
+    tokio::spawn(watch::forward(
+        listener,
+        state.services.postgres.clone(),
+        state.changes.clone(),
+    ));
+
+    tokio::spawn(outbox::relay(
+        state.services.postgres.clone(),
+        state.outbox.clone(),
+        Duration::from_millis(state.config.outbox_interval_ms),
+    ));
 
     server::serve(state, tx).await
 }
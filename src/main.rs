@@ -1,32 +1,58 @@
 use anyhow::Result;
-use categories_service::{AppConfig, state::AppState};
-use clap::Parser;
+use std::{path::PathBuf, sync::Arc};
+
+use categories_service::{AppConfig, google, outbox::LogSink, state::AppState, transfer};
+use clap::{Parser, Subcommand};
 use sellershut_services::{Configuration, Services, tracing::TracingBuilder};
 use tracing::error;
+use url::Url;
 
 /// categories-service
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
     /// Path to config file
-    #[arg(short, long)]
-    config_file: std::path::PathBuf,
+    #[arg(short, long, default_value = "categories.toml")]
+    config_file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write every category to a file, or stdout
+    Export {
+        #[arg(short, long, value_enum, default_value_t = transfer::Format::Json)]
+        format: transfer::Format,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Upsert the categories in a file written by `export`
+    Import {
+        #[arg(short, long, value_enum, default_value_t = transfer::Format::Json)]
+        format: transfer::Format,
+        input: PathBuf,
+    },
+    /// Build categories from Google's product taxonomy (`taxonomy-with-ids`)
+    ImportGoogle {
+        /// Where the `ap_id` of each new category goes, e.g. https://example.com/category
+        #[arg(short, long)]
+        base_url: Url,
+        input: PathBuf,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    #[cfg(not(debug_assertions))]
     let args = Args::parse();
 
-    let _config_path = "categories.toml";
-    #[cfg(not(debug_assertions))]
     let config_path = args
         .config_file
         .to_str()
         .expect("config file path is not valid");
 
     let config = config::Config::builder()
-        .add_source(config::File::new(_config_path, config::FileFormat::Toml))
+        .add_source(config::File::new(config_path, config::FileFormat::Toml))
         .build()
         .inspect_err(|e| error!("config file: {e}"))?;
 
@@ -47,9 +73,34 @@ async fn main() -> Result<()> {
         .inspect_err(|e| error!("database: {e}"))?
         .build();
 
-    let state = AppState::new(config.application.port, services, app_config);
+    let state = AppState::new(
+        config.application.port,
+        services,
+        app_config,
+        Arc::new(LogSink),
+    );
 
-    categories_service::run(state, tx).await?;
+    match args.command {
+        None => categories_service::run(state, tx).await?,
+        Some(Command::Export { format, output }) => {
+            categories_service::migrate(&state.services.postgres).await?;
+            let exported = transfer::export(&state.services.postgres, format).await?;
+            match output {
+                Some(path) => std::fs::write(path, exported)?,
+                None => print!("{exported}"),
+            }
+        }
+        Some(Command::Import { format, input }) => {
+            categories_service::migrate(&state.services.postgres).await?;
+            let input = std::fs::read_to_string(input)?;
+            transfer::import(&state, format, &input).await?;
+        }
+        Some(Command::ImportGoogle { base_url, input }) => {
+            categories_service::migrate(&state.services.postgres).await?;
+            let input = std::fs::read_to_string(input)?;
+            google::import(&state, &input, &base_url).await?;
+        }
+    }
 
     Ok(())
 }
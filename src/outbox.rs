@@ -0,0 +1,196 @@
+use std::{fmt, str::FromStr, sync::Mutex, time::Duration};
+
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+use time::OffsetDateTime;
+use tracing::{Instrument, debug, debug_span, error, info, warn};
+
+/// Events relayed in a single transaction
+const BATCH_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CategoryEventKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl CategoryEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CategoryEventKind::Created => "CategoryCreated",
+            CategoryEventKind::Updated => "CategoryUpdated",
+            CategoryEventKind::Deleted => "CategoryDeleted",
+        }
+    }
+}
+
+impl fmt::Display for CategoryEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CategoryEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "CategoryCreated" => Ok(CategoryEventKind::Created),
+            "CategoryUpdated" => Ok(CategoryEventKind::Updated),
+            "CategoryDeleted" => Ok(CategoryEventKind::Deleted),
+            event => Err(anyhow::anyhow!("unknown category event: {event}")),
+        }
+    }
+}
+
+/// A category change waiting in the outbox
+#[derive(Debug, Clone)]
+pub struct CategoryEvent {
+    pub id: i64,
+    pub kind: CategoryEventKind,
+    pub ap_id: String,
+    /// The category as it was after the change
+    pub payload: Value,
+    pub created_at: OffsetDateTime,
+}
+
+/// Where the relay publishes category events. An event is only removed from the
+/// outbox once `publish` succeeds, so a sink may see the same event more than once
+#[tonic::async_trait]
+pub trait OutboxSink: Send + Sync {
+    async fn publish(&self, event: &CategoryEvent) -> anyhow::Result<()>;
+}
+
+/// Writes events to the log, for when nothing downstream is listening
+#[derive(Debug, Default)]
+pub struct LogSink;
+
+#[tonic::async_trait]
+impl OutboxSink for LogSink {
+    async fn publish(&self, event: &CategoryEvent) -> anyhow::Result<()> {
+        info!(id = event.id, kind = %event.kind, ap_id = event.ap_id, "category event");
+        Ok(())
+    }
+}
+
+/// Keeps published events in memory
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    events: Mutex<Vec<CategoryEvent>>,
+}
+
+impl MemorySink {
+    pub fn events(&self) -> Vec<CategoryEvent> {
+        self.events.lock().expect("lock to not be poisoned").clone()
+    }
+}
+
+#[tonic::async_trait]
+impl OutboxSink for MemorySink {
+    async fn publish(&self, event: &CategoryEvent) -> anyhow::Result<()> {
+        self.events
+            .lock()
+            .expect("lock to not be poisoned")
+            .push(event.clone());
+        Ok(())
+    }
+}
+
+/// Queues an event for each category, carrying the category as it is now
+pub async fn enqueue(
+    conn: &mut PgConnection,
+    kind: CategoryEventKind,
+    ap_ids: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "
+        insert into category_outbox (event, category_ap_id, payload)
+        select $1, ap_id, to_jsonb(category.*)
+        from category
+        where ap_id = any($2)",
+        kind.as_str(),
+        ap_ids
+    )
+    .execute(conn)
+    .instrument(debug_span!("pg.insert.outbox"))
+    .await?;
+
+    Ok(())
+}
+
+/// Publishes queued events through `sink` until the outbox is empty, every
+/// `interval`
+pub async fn relay(pool: PgPool, sink: std::sync::Arc<dyn OutboxSink>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        loop {
+            match publish_pending(&pool, sink.as_ref()).await {
+                Ok(published) if published == BATCH_SIZE as usize => continue,
+                Ok(_) => break,
+                // the server is shutting down
+                Err(e) if matches!(e.downcast_ref(), Some(sqlx::Error::PoolClosed)) => return,
+                Err(e) => {
+                    error!("outbox relay: {e}");
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Publishes the oldest batch of queued events in order, stopping at the first
+/// one the sink fails to take so that it is retried before anything after it.
+/// Returns how many were published
+pub async fn publish_pending(pool: &PgPool, sink: &dyn OutboxSink) -> anyhow::Result<usize> {
+    let mut tx = pool.begin().await?;
+
+    // skip locked lets another relay take the next batch rather than wait
+    let rows = sqlx::query!(
+        "
+        select id, event, category_ap_id, payload, created_at
+        from category_outbox
+        order by id
+        limit $1
+        for update skip locked",
+        BATCH_SIZE
+    )
+    .fetch_all(&mut *tx)
+    .instrument(debug_span!("pg.select.outbox"))
+    .await?;
+
+    let mut published = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let event = CategoryEvent {
+            id: row.id,
+            kind: row.event.parse()?,
+            ap_id: row.category_ap_id,
+            payload: row.payload,
+            created_at: row.created_at,
+        };
+
+        if let Err(e) = sink.publish(&event).await {
+            warn!(id = event.id, "publishing category event: {e}");
+            break;
+        }
+
+        published.push(event.id);
+    }
+
+    sqlx::query!("delete from category_outbox where id = any($1)", &published)
+        .execute(&mut *tx)
+        .instrument(debug_span!("pg.delete.outbox"))
+        .await?;
+
+    tx.commit().await?;
+
+    if !published.is_empty() {
+        debug!(published = published.len(), "relayed category events");
+    }
+
+    Ok(published.len())
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use anyhow::{Context, bail};
+use sellershut_core::categories::{
+    BulkUpsertCategoriesRequest, Category, DeleteCategoryRequest, bulk_category_result::Outcome,
+    delete_category_request::Identifier, mutate_categories_server::MutateCategories,
+};
+use serde::Deserialize;
+use sqlx::PgConnection;
+use tracing::{Instrument, debug_span, info};
+
+use crate::{
+    entity,
+    state::{self, AppState},
+    transfer::{self, CategoryNode},
+    utils,
+};
+
+/// Recorded as the actor in the history of seeded categories
+const SEED_ACTOR: &str = "seed";
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SeedConfig {
+    /// TOML file declaring the category tree
+    pub file: PathBuf,
+    /// What happens to local categories the file no longer declares
+    #[serde(default)]
+    pub removed: RemovedCategories,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RemovedCategories {
+    #[default]
+    Keep,
+    Delete,
+}
+
+#[derive(Deserialize, Debug)]
+struct Taxonomy {
+    #[serde(default)]
+    categories: Vec<CategoryNode>,
+}
+
+/// What a reconcile changed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SeedSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// Declared categories left deleted, along with those declared beneath them
+    pub skipped: usize,
+}
+
+/// Brings the database in line with the taxonomy declared in the seed file.
+/// Only declared categories that are missing or differ are upserted, so an
+/// unchanged file leaves no history behind
+pub async fn reconcile(state: &AppState, config: &SeedConfig) -> anyhow::Result<SeedSummary> {
+    let taxonomy: Taxonomy = config::Config::builder()
+        .add_source(config::File::new(
+            config
+                .file
+                .to_str()
+                .context("seed file path is not valid")?,
+            config::FileFormat::Toml,
+        ))
+        .build()
+        .and_then(|file| file.try_deserialize())
+        .with_context(|| format!("reading seed file {}", config.file.display()))?;
+
+    let mut declared = Vec::new();
+    transfer::flatten(taxonomy.categories, None, &mut declared);
+
+    let mut tx = state.services.postgres.begin().await?;
+    let mut summary = apply(&mut tx, &declared, SEED_ACTOR).await?;
+
+    let declared: Vec<_> = declared.into_iter().map(|c| c.ap_id).collect();
+    sqlx::query!(
+        "insert into seeded_category (ap_id) select unnest($1::varchar[]) on conflict do nothing",
+        &declared
+    )
+    .execute(&mut *tx)
+    .instrument(debug_span!("pg.insert.seeded_category"))
+    .await?;
+
+    tx.commit().await?;
+
+    if config.removed == RemovedCategories::Delete {
+        summary.deleted = delete_undeclared(state, &declared).await?;
+    }
+
+    info!(
+        inserted = summary.inserted,
+        updated = summary.updated,
+        deleted = summary.deleted,
+        skipped = summary.skipped,
+        "reconciled seed taxonomy"
+    );
+
+    Ok(summary)
+}
+
+/// Upserts the declared categories that are missing or differ from what is
+/// stored, in one all or nothing batch. Deleted categories stay deleted until
+/// restored, so they and the categories declared beneath them are skipped.
+/// `declared` must list parents first
+pub(crate) async fn apply(
+    conn: &mut PgConnection,
+    declared: &[Category],
+    actor: &str,
+) -> anyhow::Result<SeedSummary> {
+    let ap_ids: Vec<_> = declared.iter().map(|c| c.ap_id.clone()).collect();
+    let deleted: HashSet<_> = sqlx::query_scalar!(
+        "select ap_id from category where ap_id = any($1) and deleted_at is not null",
+        &ap_ids
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.select.ap_id"))
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut skipped = HashSet::new();
+    for category in declared.iter() {
+        let beneath_skipped = category
+            .parent_id
+            .as_ref()
+            .is_some_and(|parent| skipped.contains(parent));
+        if deleted.contains(&category.ap_id) || beneath_skipped {
+            skipped.insert(category.ap_id.clone());
+        }
+    }
+    let declared: Vec<_> = declared
+        .iter()
+        .filter(|category| !skipped.contains(&category.ap_id))
+        .cloned()
+        .collect();
+
+    let existing: HashMap<_, _> = sqlx::query_as!(
+        entity::Category,
+        "select * from category where deleted_at is null"
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.select.*"))
+    .await?
+    .into_iter()
+    .map(|category| (category.ap_id.clone(), category))
+    .collect();
+
+    let changed = changed_categories(&declared, &existing);
+    let mut summary = SeedSummary {
+        skipped: skipped.len(),
+        ..Default::default()
+    };
+
+    if !changed.is_empty() {
+        let request = BulkUpsertCategoriesRequest {
+            categories: changed.clone(),
+            all_or_nothing: true,
+        };
+        let results = state::bulk_upsert(conn, Some(actor.into()), request)
+            .await?
+            .results;
+
+        let rejected: Vec<_> = changed
+            .iter()
+            .zip(results.iter())
+            .filter_map(|(category, result)| match result.outcome.as_ref() {
+                Some(Outcome::Error(error)) if error.code != tonic::Code::Aborted as i32 => {
+                    Some(format!("{}: {}", category.ap_id, error.message))
+                }
+                _ => None,
+            })
+            .collect();
+
+        if !rejected.is_empty() {
+            bail!("nothing applied, rejected:\n{}", rejected.join("\n"));
+        }
+
+        summary.inserted = results.iter().filter(|r| r.inserted).count();
+        summary.updated = results.len() - summary.inserted;
+    }
+
+    Ok(summary)
+}
+
+/// The declared categories that are missing or differ from what is stored,
+/// parents first. Each keeps the stored sub-categories that are not declared
+/// elsewhere, as an upsert would otherwise detach them
+fn changed_categories(
+    declared: &[Category],
+    existing: &HashMap<String, entity::Category>,
+) -> Vec<Category> {
+    let declared_parents: HashMap<_, _> = declared
+        .iter()
+        .map(|c| (c.ap_id.as_str(), c.parent_id.as_deref()))
+        .collect();
+
+    declared
+        .iter()
+        .filter(|category| {
+            existing.get(&category.ap_id).is_none_or(|stored| {
+                stored.name != category.name
+                    || stored.image_url != category.image_url
+                    || stored.local != category.local
+                    || stored.parent_id != category.parent_id
+            })
+        })
+        .map(|category| {
+            let parent = Some(category.ap_id.as_str());
+            let declared_subs = declared
+                .iter()
+                .filter(|c| c.parent_id.as_deref() == parent && existing.contains_key(&c.ap_id))
+                .map(|c| c.ap_id.clone());
+            let stored_subs = existing
+                .get(&category.ap_id)
+                .into_iter()
+                .flat_map(|stored| stored.sub_categories.iter())
+                .filter(|sub| {
+                    existing.contains_key(*sub)
+                        && declared_parents
+                            .get(sub.as_str())
+                            .is_none_or(|declared| *declared == parent)
+                })
+                .cloned();
+
+            let mut sub_categories: Vec<String> = Vec::new();
+            for sub in declared_subs.chain(stored_subs) {
+                if !sub_categories.contains(&sub) {
+                    sub_categories.push(sub);
+                }
+            }
+
+            Category {
+                sub_categories,
+                ..category.clone()
+            }
+        })
+        .collect()
+}
+
+/// Deletes the local categories the seed file declared before but no longer
+/// does, along with their sub-categories. Categories it never declared are left
+/// alone. Returns how many seeded categories were deleted
+async fn delete_undeclared(state: &AppState, declared: &[String]) -> anyhow::Result<usize> {
+    let seeded = sqlx::query!(
+        "
+        select c.ap_id, c.parent_id from category c
+        join seeded_category s on s.ap_id = c.ap_id
+        where c.local and c.deleted_at is null"
+    )
+    .fetch_all(&state.services.postgres)
+    .instrument(debug_span!("pg.select.ap_id"))
+    .await?;
+
+    let undeclared: HashSet<_> = seeded
+        .iter()
+        .map(|row| row.ap_id.as_str())
+        .filter(|ap_id| !declared.iter().any(|d| d == ap_id))
+        .collect();
+
+    // deleting the top of each undeclared subtree takes the rest with it
+    let roots: Vec<_> = seeded
+        .iter()
+        .filter(|row| undeclared.contains(row.ap_id.as_str()))
+        .filter(|row| {
+            row.parent_id
+                .as_deref()
+                .is_none_or(|parent| !undeclared.contains(parent))
+        })
+        .map(|row| row.ap_id.clone())
+        .collect();
+
+    for ap_id in roots.iter() {
+        let request = utils::request_as(
+            DeleteCategoryRequest {
+                identifier: Some(Identifier::ApId(ap_id.clone())),
+                ..Default::default()
+            },
+            SEED_ACTOR,
+        );
+        state.delete(request).await?;
+    }
+
+    // forget them, so restoring one later does not get it deleted again
+    sqlx::query!(
+        "delete from seeded_category where not ap_id = any($1)",
+        declared
+    )
+    .execute(&state.services.postgres)
+    .instrument(debug_span!("pg.delete.seeded_category"))
+    .await?;
+
+    Ok(undeclared.len())
+}
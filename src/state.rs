@@ -1,26 +1,47 @@
 mod mutate;
 mod query;
 
-use std::net::{Ipv6Addr, SocketAddr};
+pub(crate) use mutate::bulk_upsert;
+
+use std::{
+    net::{Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
 
 use sellershut_services::Services;
+use tokio::sync::broadcast;
 
-use crate::AppConfig;
+use crate::{
+    AppConfig,
+    outbox::OutboxSink,
+    watch::{self, WatchEvent},
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub addr: SocketAddr,
     pub services: Services,
     pub config: AppConfig,
+    pub outbox: Arc<dyn OutboxSink>,
+    /// Category changes for the watch rpc, fed by a postgres listener
+    pub changes: broadcast::Sender<WatchEvent>,
 }
 
 impl AppState {
-    pub fn new(port: u16, services: Services, config: AppConfig) -> Self {
+    pub fn new(
+        port: u16,
+        services: Services,
+        config: AppConfig,
+        outbox: Arc<dyn OutboxSink>,
+    ) -> Self {
         let listen_address = SocketAddr::from((Ipv6Addr::UNSPECIFIED, port));
+        let (changes, _) = broadcast::channel(watch::CAPACITY);
         Self {
             addr: listen_address,
             services,
             config,
+            outbox,
+            changes,
         }
     }
 }
@@ -1,14 +1,34 @@
+mod bulk;
+
+pub(crate) use bulk::upsert as bulk_upsert;
+
+use std::{collections::HashSet, slice};
+
 use sellershut_core::{
     categories::{
-        CreateCategoryRequest, CreateCategoryResponse, DeleteCategoryRequest,
-        UpsertCategoryRequest, UpsertCategoryResponse, mutate_categories_server::MutateCategories,
+        BulkUpsertCategoriesRequest, BulkUpsertCategoriesResponse, Category, CreateCategoryRequest,
+        CreateCategoryResponse, DeleteCategoryRequest, DeleteCategoryResponse,
+        PurgeCategoriesRequest, PurgeCategoriesResponse, RecordCategorySelectionsRequest,
+        RestoreCategoryRequest, RestoreCategoryResponse, SetExternalIdsRequest,
+        SetExternalIdsResponse, UpdateCategoryRequest, UpdateCategoryResponse,
+        UpsertCategoryRequest, UpsertCategoryResponse, delete_category_request::Identifier,
+        mutate_categories_server::MutateCategories,
     },
-    google::protobuf::Empty,
+    google::protobuf::{Empty, Timestamp},
 };
+use serde_json::Value;
+use sqlx::PgConnection;
+use time::OffsetDateTime;
 use tonic::{Request, Response, Status};
 use tracing::{Instrument, debug_span};
 
-use crate::{entity, utils::validate_input};
+use crate::{
+    entity,
+    outbox::{self, CategoryEventKind},
+    utils::{
+        actor, apply_field_mask, check_cycles, check_references, lock_hierarchy, validate_input,
+    },
+};
 use sellershut_services::utils::{self, ID_LENGTH};
 
 use super::AppState;
@@ -21,6 +41,7 @@ impl MutateCategories for AppState {
         &self,
         request: tonic::Request<CreateCategoryRequest>,
     ) -> Result<tonic::Response<CreateCategoryResponse>, Status> {
+        let actor = actor(request.metadata());
         let category = request
             .into_inner()
             .category
@@ -29,6 +50,38 @@ impl MutateCategories for AppState {
 
         validate_input(&category)?;
 
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+        lock_hierarchy(&mut tx).await?;
+
+        check_references(&mut tx, &category).await?;
+        check_cycles(&mut tx, &category).await?;
+
+        // a deleted category holds on to its ap_id until it is purged
+        let deleted = sqlx::query_scalar!(
+            "select deleted_at is not null as \"deleted!\" from category where ap_id = $1",
+            &category.ap_id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(debug_span!("pg.select.deleted_at"))
+        .await
+        .map_err(map_err)?;
+
+        match deleted {
+            Some(true) => {
+                return Err(Status::already_exists(format!(
+                    "category is deleted, restore it instead: {}",
+                    category.ap_id
+                )));
+            }
+            Some(false) => {
+                return Err(Status::already_exists(format!(
+                    "category already exists: {}",
+                    category.ap_id
+                )));
+            }
+            None => {}
+        }
+
         let id = utils::generate_id(ID_LENGTH);
 
         let category = sqlx::query_as!(
@@ -47,10 +100,31 @@ impl MutateCategories for AppState {
             category.local,
             category.ap_id,
         )
-        .fetch_one(&self.services.postgres)
+        .fetch_one(&mut *tx)
         .instrument(debug_span!("pg.insert"))
         .await
-        .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        .map_err(map_err)?;
+
+        let changed = sync_hierarchy(
+            &mut tx,
+            &category.ap_id,
+            category.parent_id.as_deref(),
+            &category.sub_categories,
+        )
+        .await?;
+
+        record_history(&mut tx, &category.ap_id, "create", actor.as_deref(), None).await?;
+        record_changes(&mut tx, &changed, "update", actor.as_deref()).await?;
+        outbox::enqueue(
+            &mut tx,
+            CategoryEventKind::Created,
+            slice::from_ref(&category.ap_id),
+        )
+        .await
+        .map_err(map_err)?;
+        enqueue_changed(&mut tx, &changed).await?;
+
+        tx.commit().await.map_err(map_err)?;
 
         tracing::debug!(id = %category.ap_id, "category created");
 
@@ -65,14 +139,47 @@ impl MutateCategories for AppState {
         &self,
         request: Request<UpsertCategoryRequest>,
     ) -> Result<Response<UpsertCategoryResponse>, Status> {
+        let actor = actor(request.metadata());
+        let request = request.into_inner();
         let data = request
-            .into_inner()
             .category
             .ok_or_else(|| Status::data_loss("expected category to be available"))?;
         tracing::trace!(id = %data.ap_id, name = %data.name, "upserting category");
 
         validate_input(&data)?;
 
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+        lock_hierarchy(&mut tx).await?;
+
+        check_references(&mut tx, &data).await?;
+        check_cycles(&mut tx, &data).await?;
+
+        // locking an existing row keeps the version check and reported outcome accurate
+        let existing = sqlx::query!(
+            "
+            select updated_at, deleted_at is not null as \"deleted!\"
+            from category where ap_id = $1
+            for update",
+            &data.ap_id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(debug_span!("pg.select.updated_at"))
+        .await
+        .map_err(map_err)?;
+
+        // upserting over a deleted category would bring back it alone, without
+        // the rest of its subtree or a record of the restore
+        if existing.as_ref().is_some_and(|row| row.deleted) {
+            return Err(Status::failed_precondition(format!(
+                "category is deleted, restore it instead: {}",
+                data.ap_id
+            )));
+        }
+        let existing_updated_at = existing.map(|row| row.updated_at);
+
+        check_version(request.expected_updated_at, existing_updated_at)?;
+
+        let before = snapshot(&mut tx, &data.ap_id).await?;
         let id = utils::generate_id(ID_LENGTH);
 
         let category = sqlx::query_as!(
@@ -86,7 +193,6 @@ impl MutateCategories for AppState {
                 sub_categories = excluded.sub_categories,
                 image_url = excluded.image_url,
                 parent_id = excluded.parent_id,
-                id = excluded.id,
                 local = excluded.local
                 returning *
             ",
@@ -98,14 +204,144 @@ impl MutateCategories for AppState {
             &data.local,
             data.ap_id,
         )
-        .fetch_one(&self.services.postgres)
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|e| Status::internal(e.to_string()))?;
+        .map_err(map_err)?;
 
-        tracing::debug!(id = %data.ap_id, name = %category.name, "category upserted");
+        let changed = sync_hierarchy(
+            &mut tx,
+            &category.ap_id,
+            category.parent_id.as_deref(),
+            &category.sub_categories,
+        )
+        .await?;
+
+        record_history(&mut tx, &category.ap_id, "upsert", actor.as_deref(), before).await?;
+        record_changes(&mut tx, &changed, "update", actor.as_deref()).await?;
+
+        let inserted = existing_updated_at.is_none();
+        let kind = if inserted {
+            CategoryEventKind::Created
+        } else {
+            CategoryEventKind::Updated
+        };
+        outbox::enqueue(&mut tx, kind, slice::from_ref(&category.ap_id))
+            .await
+            .map_err(map_err)?;
+        enqueue_changed(&mut tx, &changed).await?;
+
+        tx.commit().await.map_err(map_err)?;
+
+        tracing::debug!(id = %data.ap_id, name = %category.name, inserted, "category upserted");
 
         Ok(Response::new(UpsertCategoryResponse {
             category: Some(category.into()),
+            inserted,
+        }))
+    }
+
+    #[doc = " Upsert many categories at once, reporting the outcome of each"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn bulk_upsert(
+        &self,
+        request: Request<BulkUpsertCategoriesRequest>,
+    ) -> Result<Response<BulkUpsertCategoriesResponse>, Status> {
+        let actor = actor(request.metadata());
+
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+        let response = bulk::upsert(&mut tx, actor, request.into_inner()).await?;
+        tx.commit().await.map_err(map_err)?;
+
+        Ok(Response::new(response))
+    }
+
+    #[doc = " Update the fields of a category listed in the update mask"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn update(
+        &self,
+        request: Request<UpdateCategoryRequest>,
+    ) -> Result<Response<UpdateCategoryResponse>, Status> {
+        let actor = actor(request.metadata());
+        let request = request.into_inner();
+        let update = request
+            .category
+            .ok_or_else(|| Status::data_loss("expected category to be available"))?;
+        let mask = request
+            .update_mask
+            .ok_or_else(|| Status::invalid_argument("expected update_mask to be available"))?;
+        tracing::trace!(id = %request.ap_id, fields = ?mask.paths, "updating category");
+
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+        lock_hierarchy(&mut tx).await?;
+
+        let existing = sqlx::query_as!(
+            entity::Category,
+            "select * from category where ap_id = $1 and deleted_at is null for update",
+            &request.ap_id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| Status::not_found(format!("category not found: {}", request.ap_id)))?;
+
+        check_version(request.expected_updated_at, Some(existing.updated_at))?;
+
+        let before = snapshot(&mut tx, &existing.ap_id).await?;
+        let mut data = Category::from(existing);
+        apply_field_mask(&mut data, &update, &mask)?;
+
+        validate_input(&data)?;
+        check_references(&mut tx, &data).await?;
+        check_cycles(&mut tx, &data).await?;
+
+        let category = sqlx::query_as!(
+            entity::Category,
+            "
+                update category
+                set name = $2,
+                sub_categories = $3,
+                image_url = $4,
+                parent_id = $5
+                where ap_id = $1
+                returning *
+            ",
+            &data.ap_id,
+            &data.name,
+            &data.sub_categories,
+            data.image_url,
+            data.parent_id,
+        )
+        .fetch_one(&mut *tx)
+        .instrument(debug_span!("pg.update"))
+        .await
+        .map_err(map_err)?;
+
+        let changed = sync_hierarchy(
+            &mut tx,
+            &category.ap_id,
+            category.parent_id.as_deref(),
+            &category.sub_categories,
+        )
+        .await?;
+
+        record_history(&mut tx, &category.ap_id, "update", actor.as_deref(), before).await?;
+        record_changes(&mut tx, &changed, "update", actor.as_deref()).await?;
+        outbox::enqueue(
+            &mut tx,
+            CategoryEventKind::Updated,
+            slice::from_ref(&category.ap_id),
+        )
+        .await
+        .map_err(map_err)?;
+        enqueue_changed(&mut tx, &changed).await?;
+
+        tx.commit().await.map_err(map_err)?;
+
+        tracing::debug!(id = %category.ap_id, name = %category.name, "category updated");
+
+        Ok(Response::new(UpdateCategoryResponse {
+            category: Some(category.into()),
         }))
     }
 
@@ -114,19 +350,617 @@ impl MutateCategories for AppState {
     async fn delete(
         &self,
         request: Request<DeleteCategoryRequest>,
-    ) -> Result<Response<Empty>, Status> {
+    ) -> Result<Response<DeleteCategoryResponse>, Status> {
+        let actor = actor(request.metadata());
+        let request = request.into_inner();
+        let (ap_id, id) = match request.identifier {
+            Some(Identifier::ApId(ap_id)) => (Some(ap_id), None),
+            Some(Identifier::Id(id)) => (None, Some(id)),
+            None => return Err(Status::invalid_argument("ap_id or id is required")),
+        };
+        tracing::trace!(ap_id = ap_id, id = id, "deleting category");
+
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+        lock_hierarchy(&mut tx).await?;
+
+        let category = sqlx::query_as!(
+            entity::Category,
+            "
+            select * from category
+            where (ap_id = $1 or id = $2) and deleted_at is null
+            for update",
+            ap_id,
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| {
+            Status::not_found(format!(
+                "category not found: {}",
+                ap_id.or(id).unwrap_or_default()
+            ))
+        })?;
+
+        check_version(request.expected_updated_at, Some(category.updated_at))?;
+
+        // the parent stops listing it, while its own sub-categories are deleted
+        // alongside it and keep listing each other for a restore
+        let parents = sqlx::query_as!(
+            Changed,
+            "
+            update category c set sub_categories = array_remove(c.sub_categories, $1)
+            from category old
+            where old.ap_id = c.ap_id and $1 = any(c.sub_categories)
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+            &category.ap_id
+        )
+        .fetch_all(&mut *tx)
+        .instrument(debug_span!("pg.update.parent"))
+        .await
+        .map_err(map_err)?;
+
+        let deleted = sqlx::query_as!(
+            Changed,
+            "
+            with recursive subtree (ap_id, path) as (
+                select ap_id, array[ap_id]
+                from category
+                where ap_id = $1 and deleted_at is null
+                union all
+                select c.ap_id, s.path || c.ap_id
+                from category c
+                join subtree s on c.parent_id = s.ap_id
+                where c.deleted_at is null and not c.ap_id = any(s.path)
+            )
+            update category c set deleted_at = current_timestamp
+            from category old
+            where old.ap_id = c.ap_id and c.ap_id in (select ap_id from subtree)
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+            &category.ap_id
+        )
+        .fetch_all(&mut *tx)
+        .instrument(debug_span!("pg.update.deleted_at"))
+        .await
+        .map_err(map_err)?;
+
+        record_changes(&mut tx, &deleted, "delete", actor.as_deref()).await?;
+        record_changes(&mut tx, &parents, "update", actor.as_deref()).await?;
+
+        let deleted: Vec<_> = deleted.into_iter().map(|c| c.ap_id).collect();
+        outbox::enqueue(&mut tx, CategoryEventKind::Deleted, &deleted)
+            .await
+            .map_err(map_err)?;
+        enqueue_changed(&mut tx, &parents).await?;
+
+        tx.commit().await.map_err(map_err)?;
+
+        tracing::debug!(id = %category.ap_id, "category deleted");
+
+        Ok(Response::new(DeleteCategoryResponse {
+            category: Some(category.into()),
+        }))
+    }
+
+    #[doc = " Restore a deleted category along with the sub-categories deleted with it"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn restore(
+        &self,
+        request: Request<RestoreCategoryRequest>,
+    ) -> Result<Response<RestoreCategoryResponse>, Status> {
+        let actor = actor(request.metadata());
         let id = request.into_inner().ap_id;
-        tracing::trace!(id = id, "deleting category");
+        tracing::trace!(id = id, "restoring category");
 
-        sqlx::query!(
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+        lock_hierarchy(&mut tx).await?;
+
+        let deleted = sqlx::query_as!(
+            entity::Category,
+            "select * from category where ap_id = $1 and deleted_at is not null for update",
+            &id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| Status::not_found(format!("deleted category not found: {id}")))?;
+
+        if let Some(ref parent_id) = deleted.parent_id {
+            let parent_deleted = sqlx::query_scalar!(
+                "select deleted_at is not null as \"deleted!\" from category where ap_id = $1",
+                parent_id
+            )
+            .fetch_one(&mut *tx)
+            .instrument(debug_span!("pg.select.deleted_at"))
+            .await
+            .map_err(map_err)?;
+
+            if parent_deleted {
+                return Err(Status::failed_precondition(format!(
+                    "parent category is deleted, restore it first: {parent_id}"
+                )));
+            }
+        }
+
+        let restored = sqlx::query_as!(
+            Changed,
             "
-            delete from category where ap_id = $1",
+            with recursive subtree (ap_id, path) as (
+                select ap_id, array[ap_id]
+                from category
+                where ap_id = $1
+                union all
+                select c.ap_id, s.path || c.ap_id
+                from category c
+                join subtree s on c.parent_id = s.ap_id
+                where c.deleted_at = $2 and not c.ap_id = any(s.path)
+            )
+            update category c set deleted_at = null
+            from category old
+            where old.ap_id = c.ap_id and c.ap_id in (select ap_id from subtree)
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+            &id,
+            deleted.deleted_at
+        )
+        .fetch_all(&mut *tx)
+        .instrument(debug_span!("pg.update.deleted_at"))
+        .await
+        .map_err(map_err)?;
+
+        let mut parents = sync_hierarchy(
+            &mut tx,
+            &deleted.ap_id,
+            deleted.parent_id.as_deref(),
+            &deleted.sub_categories,
+        )
+        .await?;
+        parents.retain(|parent| !restored.iter().any(|c| c.ap_id == parent.ap_id));
+
+        let category = sqlx::query_as!(
+            entity::Category,
+            "select * from category where ap_id = $1",
             &id
         )
+        .fetch_one(&mut *tx)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?;
+
+        record_changes(&mut tx, &restored, "restore", actor.as_deref()).await?;
+        record_changes(&mut tx, &parents, "update", actor.as_deref()).await?;
+
+        // downstream services treat restored categories as new again
+        let restored: Vec<_> = restored.into_iter().map(|c| c.ap_id).collect();
+        outbox::enqueue(&mut tx, CategoryEventKind::Created, &restored)
+            .await
+            .map_err(map_err)?;
+        enqueue_changed(&mut tx, &parents).await?;
+
+        tx.commit().await.map_err(map_err)?;
+
+        tracing::debug!(id = %category.ap_id, "category restored");
+
+        Ok(Response::new(RestoreCategoryResponse {
+            category: Some(category.into()),
+        }))
+    }
+
+    #[doc = " Permanently remove categories deleted longer ago than the retention period"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn purge(
+        &self,
+        request: Request<PurgeCategoriesRequest>,
+    ) -> Result<Response<PurgeCategoriesResponse>, Status> {
+        let actor = actor(request.metadata());
+        let retention_days = self.config.deleted_retention_days;
+        tracing::trace!(retention_days, "purging deleted categories");
+
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+
+        let purged = sqlx::query!(
+            "
+            delete from category
+            where deleted_at < current_timestamp - make_interval(days => $1)
+            returning ap_id, to_jsonb(category.*) as \"before!\"",
+            retention_days
+        )
+        .fetch_all(&mut *tx)
+        .instrument(debug_span!("pg.delete"))
+        .await
+        .map_err(map_err)?;
+
+        let ids: Vec<_> = purged
+            .iter()
+            .map(|_| utils::generate_id(ID_LENGTH))
+            .collect();
+        let (ap_ids, befores): (Vec<_>, Vec<_>) = purged
+            .into_iter()
+            .map(|category| (category.ap_id, category.before))
+            .unzip();
+
+        sqlx::query!(
+            "
+            insert into category_history (id, category_ap_id, operation, actor, before)
+            select id, ap_id, 'purge', $4, before
+            from unnest($1::varchar[], $2::varchar[], $3::jsonb[]) as p(id, ap_id, before)",
+            &ids,
+            &ap_ids,
+            &befores,
+            actor,
+        )
+        .execute(&mut *tx)
+        .instrument(debug_span!("pg.insert.history"))
+        .await
+        .map_err(map_err)?;
+
+        tx.commit().await.map_err(map_err)?;
+
+        tracing::debug!(purged = ap_ids.len(), "purged deleted categories");
+
+        Ok(Response::new(PurgeCategoriesResponse {
+            purged: ap_ids.len() as u64,
+        }))
+    }
+
+    #[doc = " Set or clear the ids of a category in external taxonomies"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn set_external_ids(
+        &self,
+        request: Request<SetExternalIdsRequest>,
+    ) -> Result<Response<SetExternalIdsResponse>, Status> {
+        let request = request.into_inner();
+        let ap_id = request.ap_id;
+        tracing::trace!(id = ap_id, "setting external ids");
+
+        let mut taxonomies = Vec::with_capacity(request.external_ids.len());
+        let mut external_ids = Vec::with_capacity(request.external_ids.len());
+        for external_id in request.external_ids.iter() {
+            let taxonomy = entity::taxonomy_name(external_id.taxonomy())
+                .ok_or_else(|| Status::invalid_argument("taxonomy is required"))?;
+            if taxonomies.contains(&taxonomy) {
+                return Err(Status::invalid_argument(format!(
+                    "taxonomy listed more than once: {taxonomy}"
+                )));
+            }
+            taxonomies.push(taxonomy);
+            external_ids.push(external_id.external_id.trim().to_string());
+        }
+        let taxonomies: Vec<_> = taxonomies.into_iter().map(String::from).collect();
+
+        let mut tx = self.services.postgres.begin().await.map_err(map_err)?;
+
+        sqlx::query_scalar!(
+            "select ap_id from category where ap_id = $1 and deleted_at is null for update",
+            &ap_id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(debug_span!("pg.select.ap_id"))
+        .await
+        .map_err(map_err)?
+        .ok_or_else(|| Status::not_found(format!("category not found: {ap_id}")))?;
+
+        sqlx::query!(
+            "
+            delete from category_external_id
+            where category_ap_id = $1 and taxonomy = any($2)",
+            &ap_id,
+            &taxonomies
+        )
+        .execute(&mut *tx)
+        .instrument(debug_span!("pg.delete.external_id"))
+        .await
+        .map_err(map_err)?;
+
+        sqlx::query!(
+            "
+            insert into category_external_id (taxonomy, external_id, category_ap_id)
+            select v.taxonomy, v.external_id, $3
+            from unnest($1::varchar[], $2::varchar[]) as v (taxonomy, external_id)
+            where v.external_id <> ''",
+            &taxonomies,
+            &external_ids,
+            &ap_id
+        )
+        .execute(&mut *tx)
+        .instrument(debug_span!("pg.insert.external_id"))
+        .await
+        .map_err(|err| match err.as_database_error() {
+            Some(e) if e.is_unique_violation() => {
+                Status::already_exists("external id is mapped to another category")
+            }
+            _ => map_err(err),
+        })?;
+
+        let external_ids = sqlx::query_as!(
+            entity::CategoryExternalId,
+            "
+            select taxonomy, external_id from category_external_id
+            where category_ap_id = $1
+            order by taxonomy",
+            &ap_id
+        )
+        .fetch_all(&mut *tx)
+        .instrument(debug_span!("pg.select.external_id"))
+        .await
+        .map_err(map_err)?;
+
+        tx.commit().await.map_err(map_err)?;
+
+        Ok(Response::new(SetExternalIdsResponse {
+            external_ids: external_ids.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    #[doc = " Count categories picked from autocomplete suggestions towards their popularity"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn record_selections(
+        &self,
+        request: Request<RecordCategorySelectionsRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        let ap_ids = request.into_inner().ap_ids;
+        tracing::trace!(count = ap_ids.len(), "recording selections");
+
+        // a category may be gone by the time it is picked, which is not worth failing over
+        sqlx::query!(
+            "
+            insert into category_rank (category_ap_id, name, popularity)
+            select c.ap_id, c.name, count(*)
+            from unnest($1::varchar[]) as v (ap_id)
+            join category c on c.ap_id = v.ap_id
+            where c.deleted_at is null
+            group by c.ap_id, c.name
+            on conflict (category_ap_id) do update
+            set popularity = category_rank.popularity + excluded.popularity",
+            &ap_ids
+        )
         .execute(&self.services.postgres)
+        .instrument(debug_span!("pg.insert.category_rank"))
         .await
-        .map_err(|e| Status::internal(e.to_string()))?;
+        .map_err(map_err)?;
 
         Ok(Response::new(Empty::default()))
     }
 }
+
+/// Rejects a mutation made against a version of the category that is no longer
+/// current, if the caller gave the `updated_at` it last read
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+fn check_version(
+    expected_updated_at: Option<Timestamp>,
+    updated_at: Option<OffsetDateTime>,
+) -> Result<(), Status> {
+    let Some(expected_updated_at) = expected_updated_at else {
+        return Ok(());
+    };
+
+    let expected_updated_at = OffsetDateTime::try_from(expected_updated_at)
+        .map_err(|_| Status::invalid_argument("expected_updated_at is invalid"))?;
+
+    match updated_at {
+        Some(updated_at) if updated_at == expected_updated_at => Ok(()),
+        Some(updated_at) => Err(Status::aborted(format!(
+            "category was modified at {updated_at}, after the expected version"
+        ))),
+        None => Err(Status::aborted("category no longer exists")),
+    }
+}
+
+/// Updates the other side of a category's parent and sub-category links so that
+/// parents list exactly the categories whose `parent_id` points at them.
+/// Returns the other categories this changed, as they were before
+async fn sync_hierarchy(
+    conn: &mut PgConnection,
+    ap_id: &str,
+    parent_id: Option<&str>,
+    sub_categories: &[String],
+) -> Result<Vec<Changed>, Status> {
+    let mut changed = Vec::new();
+
+    // leave any previous parent
+    changed.extend(
+        sqlx::query_as!(
+            Changed,
+            "
+            update category c set sub_categories = array_remove(c.sub_categories, $1)
+            from category old
+            where old.ap_id = c.ap_id
+                and $1 = any(c.sub_categories) and c.ap_id is distinct from $2
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+            ap_id,
+            parent_id
+        )
+        .fetch_all(&mut *conn)
+        .instrument(debug_span!("pg.update.parent"))
+        .await
+        .map_err(map_err)?,
+    );
+
+    if let Some(parent_id) = parent_id {
+        changed.extend(
+            sqlx::query_as!(
+                Changed,
+                "
+            update category c set sub_categories = array_append(c.sub_categories, $1)
+            from category old
+            where old.ap_id = c.ap_id and c.ap_id = $2 and not ($1 = any(c.sub_categories))
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+                ap_id,
+                parent_id
+            )
+            .fetch_all(&mut *conn)
+            .instrument(debug_span!("pg.update.parent"))
+            .await
+            .map_err(map_err)?,
+        );
+    }
+
+    // sub-categories that are no longer listed become top-level categories
+    changed.extend(
+        sqlx::query_as!(
+            Changed,
+            "
+            update category c set parent_id = null
+            from category old
+            where old.ap_id = c.ap_id
+                and c.parent_id = $1 and not (c.ap_id = any($2)) and c.deleted_at is null
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+            ap_id,
+            sub_categories
+        )
+        .fetch_all(&mut *conn)
+        .instrument(debug_span!("pg.update.sub_categories"))
+        .await
+        .map_err(map_err)?,
+    );
+
+    if !sub_categories.is_empty() {
+        // newly listed sub-categories move away from their previous parent
+        changed.extend(
+            sqlx::query_as!(
+                Changed,
+                "
+            update category c set sub_categories = array(
+                select sub from unnest(c.sub_categories) as sub where sub <> all($2::varchar[])
+            )
+            from category old
+            where old.ap_id = c.ap_id and c.ap_id <> $1 and c.sub_categories && $2::varchar[]
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+                ap_id,
+                sub_categories
+            )
+            .fetch_all(&mut *conn)
+            .instrument(debug_span!("pg.update.sub_categories"))
+            .await
+            .map_err(map_err)?,
+        );
+
+        changed.extend(
+            sqlx::query_as!(
+                Changed,
+                "
+            update category c set parent_id = $1
+            from category old
+            where old.ap_id = c.ap_id and c.ap_id = any($2) and c.parent_id is distinct from $1
+            returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+                ap_id,
+                sub_categories
+            )
+            .fetch_all(&mut *conn)
+            .instrument(debug_span!("pg.update.sub_categories"))
+            .await
+            .map_err(map_err)?,
+        );
+    }
+
+    // a category changed twice keeps how it was before the first change
+    let mut seen = HashSet::new();
+    changed.retain(|c| seen.insert(c.ap_id.clone()));
+
+    Ok(changed)
+}
+
+fn map_err(err: sqlx::Error) -> Status {
+    match err.as_database_error() {
+        // a referenced category was removed after it was checked
+        Some(e) if e.is_foreign_key_violation() => Status::failed_precondition(e.to_string()),
+        _ => Status::internal(err.to_string()),
+    }
+}
+
+/// The category as it is stored right now, for the category history
+async fn snapshot(conn: &mut PgConnection, ap_id: &str) -> Result<Option<Value>, Status> {
+    sqlx::query_scalar!(
+        "select to_jsonb(category.*) as \"category!\" from category where ap_id = $1",
+        ap_id
+    )
+    .fetch_optional(conn)
+    .instrument(debug_span!("pg.select.to_jsonb"))
+    .await
+    .map_err(map_err)
+}
+
+/// A category as it was before a statement changed it
+struct Changed {
+    ap_id: String,
+    before: Value,
+}
+
+/// Appends an entry to the history of each changed category, taking the
+/// categories as they are now as the state after the change
+async fn record_changes(
+    conn: &mut PgConnection,
+    changed: &[Changed],
+    operation: &str,
+    actor: Option<&str>,
+) -> Result<(), Status> {
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<_> = changed
+        .iter()
+        .map(|_| utils::generate_id(ID_LENGTH))
+        .collect();
+    let (ap_ids, befores): (Vec<_>, Vec<_>) = changed
+        .iter()
+        .map(|c| (c.ap_id.clone(), c.before.clone()))
+        .unzip();
+
+    sqlx::query!(
+        "
+        insert into category_history (id, category_ap_id, operation, actor, before, after)
+        select v.id, v.ap_id, $4, $5, v.before, to_jsonb(c.*)
+        from unnest($1::varchar[], $2::varchar[], $3::jsonb[]) as v (id, ap_id, before)
+        join category c on c.ap_id = v.ap_id",
+        &ids,
+        &ap_ids,
+        &befores,
+        operation,
+        actor,
+    )
+    .execute(conn)
+    .instrument(debug_span!("pg.insert.history"))
+    .await
+    .map_err(map_err)?;
+
+    Ok(())
+}
+
+/// Queues an update event for the categories a mutation changed on the side, so
+/// that downstream services see their new parent or sub-categories
+async fn enqueue_changed(conn: &mut PgConnection, changed: &[Changed]) -> Result<(), Status> {
+    let ap_ids: Vec<_> = changed.iter().map(|c| c.ap_id.clone()).collect();
+
+    outbox::enqueue(conn, CategoryEventKind::Updated, &ap_ids)
+        .await
+        .map_err(map_err)
+}
+
+/// Appends an entry to a category's history, taking the category as it is now
+/// as the state after the change
+async fn record_history(
+    conn: &mut PgConnection,
+    ap_id: &str,
+    operation: &str,
+    actor: Option<&str>,
+    before: Option<Value>,
+) -> Result<(), Status> {
+    sqlx::query!(
+        "
+        insert into category_history (id, category_ap_id, operation, actor, before, after)
+        values ($1, $2::varchar, $3, $4, $5, (select to_jsonb(category.*) from category where ap_id = $2::varchar))",
+        utils::generate_id(ID_LENGTH),
+        ap_id,
+        operation,
+        actor,
+        before,
+    )
+    .execute(conn)
+    .instrument(debug_span!("pg.insert.history"))
+    .await
+    .map_err(map_err)?;
+
+    Ok(())
+}
@@ -0,0 +1,502 @@
+use std::collections::{HashMap, HashSet};
+
+use sellershut_core::categories::{
+    BulkCategoryError, BulkCategoryResult, BulkUpsertCategoriesRequest,
+    BulkUpsertCategoriesResponse, Category, bulk_category_result::Outcome,
+};
+use sellershut_services::utils::{self, ID_LENGTH};
+use serde_json::Value;
+use sqlx::PgConnection;
+use tonic::Status;
+use tracing::{Instrument, debug_span};
+
+use crate::{
+    entity,
+    outbox::{self, CategoryEventKind},
+    utils::{lock_hierarchy, validate_input},
+};
+
+use super::{Changed, enqueue_changed, map_err, record_changes};
+
+/// Upserts every acceptable category in a handful of statements, as if each had
+/// been upserted on its own in the order given. A category may only refer to
+/// categories that already exist or that come before it in the batch. Nothing is
+/// written when an all or nothing batch is rejected
+pub(crate) async fn upsert(
+    conn: &mut PgConnection,
+    actor: Option<String>,
+    request: BulkUpsertCategoriesRequest,
+) -> Result<BulkUpsertCategoriesResponse, Status> {
+    let categories = request.categories;
+    tracing::trace!(count = categories.len(), "bulk upserting categories");
+
+    let mut rejections: Vec<Option<Status>> = Vec::with_capacity(categories.len());
+    let mut seen = HashSet::with_capacity(categories.len());
+    for category in categories.iter() {
+        let rejection = if !seen.insert(category.ap_id.as_str()) {
+            Some(Status::invalid_argument(format!(
+                "category appears more than once in the batch: {}",
+                category.ap_id
+            )))
+        } else {
+            validate_input(category).err()
+        };
+        rejections.push(rejection);
+    }
+
+    let ap_ids: Vec<_> = categories.iter().map(|c| c.ap_id.clone()).collect();
+    let mut references: Vec<_> = categories
+        .iter()
+        .flat_map(|c| c.parent_id.iter().chain(c.sub_categories.iter()))
+        .cloned()
+        .collect();
+    references.extend(ap_ids.iter().cloned());
+
+    // the hierarchy is checked against what is stored, so keep it from moving
+    lock_hierarchy(&mut *conn).await?;
+
+    // locking the rows keeps the snapshots and reported outcome accurate
+    let existing = sqlx::query!(
+        "
+        select ap_id, deleted_at is null as \"live!\", to_jsonb(category.*) as \"snapshot!\"
+        from category where ap_id = any($1)
+        for update",
+        &ap_ids
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.select.to_jsonb"))
+    .await
+    .map_err(map_err)?;
+
+    let mut live: HashSet<_> = sqlx::query_scalar!(
+        "select ap_id from category where ap_id = any($1) and deleted_at is null",
+        &references
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.select.ap_id"))
+    .await
+    .map_err(map_err)?
+    .into_iter()
+    .collect();
+
+    let deleted: HashSet<_> = existing
+        .iter()
+        .filter(|row| !row.live)
+        .map(|row| row.ap_id.clone())
+        .collect();
+
+    let mut parents = load_parents(&mut *conn, &references).await?;
+    let mut touched = HashSet::new();
+    let mut affected_parents: HashSet<String> = HashSet::new();
+
+    for (category, rejection) in categories.iter().zip(rejections.iter_mut()) {
+        if rejection.is_some() {
+            continue;
+        }
+
+        // as with a single upsert, a deleted category is only brought back by restore
+        if deleted.contains(&category.ap_id) {
+            *rejection = Some(Status::failed_precondition(format!(
+                "category is deleted, restore it instead: {}",
+                category.ap_id
+            )));
+            continue;
+        }
+
+        if let Err(status) = check_item(category, &live, &parents) {
+            *rejection = Some(status);
+            continue;
+        }
+
+        // apply the item to the hierarchy so later items see it
+        let mut moved = vec![(category.ap_id.clone(), category.parent_id.clone())];
+        moved.extend(
+            category
+                .sub_categories
+                .iter()
+                .map(|sub| (sub.clone(), Some(category.ap_id.clone()))),
+        );
+        moved.extend(
+            parents
+                .iter()
+                .filter(|(child, parent)| {
+                    parent.as_deref() == Some(category.ap_id.as_str())
+                        && !category.sub_categories.contains(child)
+                })
+                .map(|(child, _)| (child.clone(), None)),
+        );
+
+        for (child, parent) in moved {
+            let previous = parents.insert(child.clone(), parent.clone());
+            affected_parents.extend(previous.flatten());
+            affected_parents.extend(parent);
+            touched.insert(child);
+        }
+
+        affected_parents.insert(category.ap_id.clone());
+        live.insert(category.ap_id.clone());
+    }
+
+    let rejected = rejections.iter().filter(|r| r.is_some()).count();
+
+    if request.all_or_nothing && rejected > 0 {
+        let results = rejections
+            .into_iter()
+            .map(|rejection| {
+                rejected_result(rejection.unwrap_or_else(|| {
+                    Status::aborted("not applied, other categories in the batch were rejected")
+                }))
+            })
+            .collect();
+
+        return Ok(BulkUpsertCategoriesResponse { results });
+    }
+
+    let accepted: Vec<_> = categories
+        .iter()
+        .zip(rejections.iter())
+        .filter(|(_, rejection)| rejection.is_none())
+        .map(|(category, _)| category)
+        .collect();
+
+    let existing: HashMap<_, _> = existing
+        .into_iter()
+        .filter(|row| row.live)
+        .map(|row| (row.ap_id, row.snapshot))
+        .collect();
+
+    if !accepted.is_empty() {
+        insert(&mut *conn, &accepted).await?;
+        let order: Vec<_> = accepted.iter().map(|c| c.ap_id.clone()).collect();
+        let changed =
+            sync_hierarchy(&mut *conn, &parents, &touched, &affected_parents, &order).await?;
+
+        let befores: Vec<_> = accepted
+            .iter()
+            .map(|c| existing.get(&c.ap_id).cloned())
+            .collect();
+        record_history(&mut *conn, &accepted, befores, actor.as_deref()).await?;
+
+        let (inserted, updated): (Vec<_>, Vec<_>) = accepted
+            .iter()
+            .map(|c| c.ap_id.clone())
+            .partition(|ap_id| !existing.contains_key(ap_id));
+
+        outbox::enqueue(&mut *conn, CategoryEventKind::Created, &inserted)
+            .await
+            .map_err(map_err)?;
+        outbox::enqueue(&mut *conn, CategoryEventKind::Updated, &updated)
+            .await
+            .map_err(map_err)?;
+
+        // categories outside the batch that gained or lost a parent or sub-category
+        let changed: Vec<_> = changed
+            .into_iter()
+            .filter(|c| !order.contains(&c.ap_id))
+            .collect();
+        record_changes(&mut *conn, &changed, "update", actor.as_deref()).await?;
+        enqueue_changed(&mut *conn, &changed).await?;
+    }
+
+    let accepted_ap_ids: Vec<_> = accepted.iter().map(|c| c.ap_id.clone()).collect();
+    let mut stored: HashMap<_, _> = sqlx::query_as!(
+        entity::Category,
+        "select * from category where ap_id = any($1)",
+        &accepted_ap_ids
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.select.*"))
+    .await
+    .map_err(map_err)?
+    .into_iter()
+    .map(|category| (category.ap_id.clone(), category))
+    .collect();
+
+    tracing::debug!(
+        upserted = accepted.len(),
+        rejected,
+        "bulk upserted categories"
+    );
+
+    let results = categories
+        .into_iter()
+        .zip(rejections)
+        .map(|(category, rejection)| match rejection {
+            Some(rejection) => rejected_result(rejection),
+            None => BulkCategoryResult {
+                inserted: !existing.contains_key(&category.ap_id),
+                outcome: stored
+                    .remove(&category.ap_id)
+                    .map(|category| Outcome::Category(category.into())),
+            },
+        })
+        .collect();
+
+    Ok(BulkUpsertCategoriesResponse { results })
+}
+
+fn rejected_result(status: Status) -> BulkCategoryResult {
+    BulkCategoryResult {
+        outcome: Some(Outcome::Error(BulkCategoryError {
+            code: status.code().into(),
+            message: status.message().to_string(),
+        })),
+        inserted: false,
+    }
+}
+
+/// The parents of the given categories, their ancestors and their current
+/// sub-categories, as they are stored now
+async fn load_parents(
+    conn: &mut PgConnection,
+    ap_ids: &[String],
+) -> Result<HashMap<String, Option<String>>, Status> {
+    let rows = sqlx::query!(
+        "
+        with recursive ancestors (ap_id, parent_id) as (
+            select ap_id, parent_id
+            from category
+            where ap_id = any($1) and deleted_at is null
+            union
+            select c.ap_id, c.parent_id
+            from category c
+            join ancestors a on c.ap_id = a.parent_id
+        )
+        select ap_id as \"ap_id!\", parent_id from ancestors
+        union
+        select ap_id, parent_id from category
+        where parent_id = any($1) and deleted_at is null",
+        ap_ids
+    )
+    .fetch_all(conn)
+    .instrument(debug_span!("pg.select.ancestors"))
+    .await
+    .map_err(map_err)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.ap_id, row.parent_id))
+        .collect())
+}
+
+/// The batch equivalent of `check_references` and `check_cycles`
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+fn check_item(
+    category: &Category,
+    live: &HashSet<String>,
+    parents: &HashMap<String, Option<String>>,
+) -> Result<(), Status> {
+    if let Some(parent_id) = category.parent_id.as_ref().filter(|p| !live.contains(*p)) {
+        return Err(Status::failed_precondition(format!(
+            "parent category does not exist: {parent_id}"
+        )));
+    }
+
+    if let Some(missing) = category.sub_categories.iter().find(|s| !live.contains(*s)) {
+        return Err(Status::failed_precondition(format!(
+            "sub-category does not exist: {missing}"
+        )));
+    }
+
+    let cycle_err = |path: &[&str]| {
+        Status::invalid_argument(format!(
+            "category would become its own ancestor: {}",
+            path.join(" -> ")
+        ))
+    };
+
+    let ap_id = category.ap_id.as_str();
+    if category.sub_categories.iter().any(|s| s == ap_id) {
+        return Err(cycle_err(&[ap_id, ap_id]));
+    }
+
+    let mut path = vec![ap_id];
+    let mut ancestor = category.parent_id.as_deref();
+    while let Some(current) = ancestor {
+        path.push(current);
+        if current == ap_id {
+            return Err(cycle_err(&path));
+        }
+        // the sub-category would move beneath this category
+        if category.sub_categories.iter().any(|s| s == current) {
+            path.push(ap_id);
+            return Err(cycle_err(&path));
+        }
+        // an existing cycle is not this category's to report
+        if path[1..path.len() - 1].contains(&current) {
+            break;
+        }
+        ancestor = parents.get(current).and_then(|p| p.as_deref());
+    }
+
+    Ok(())
+}
+
+async fn insert(conn: &mut PgConnection, categories: &[&Category]) -> Result<(), Status> {
+    let ids: Vec<_> = categories
+        .iter()
+        .map(|_| utils::generate_id(ID_LENGTH))
+        .collect();
+    let names: Vec<_> = categories.iter().map(|c| c.name.clone()).collect();
+    // arrays of arrays cannot be unnested a row at a time, so each goes as json
+    let sub_categories: Vec<_> = categories
+        .iter()
+        .map(|c| Value::from(c.sub_categories.clone()))
+        .collect();
+    let image_urls: Vec<_> = categories.iter().map(|c| c.image_url.clone()).collect();
+    let parent_ids: Vec<_> = categories.iter().map(|c| c.parent_id.clone()).collect();
+    let locals: Vec<_> = categories.iter().map(|c| c.local).collect();
+    let ap_ids: Vec<_> = categories.iter().map(|c| c.ap_id.clone()).collect();
+
+    sqlx::query!(
+        "
+        insert into category (id, name, sub_categories, image_url, parent_id, local, ap_id)
+        select
+            v.id,
+            v.name,
+            array(select jsonb_array_elements_text(v.sub_categories))::varchar[],
+            v.image_url,
+            v.parent_id,
+            v.local,
+            v.ap_id
+        from unnest(
+            $1::varchar[],
+            $2::varchar[],
+            $3::jsonb[],
+            $4::varchar[],
+            $5::varchar[],
+            $6::bool[],
+            $7::varchar[]
+        ) as v (id, name, sub_categories, image_url, parent_id, local, ap_id)
+        on conflict (ap_id)
+        do update
+        set name = excluded.name,
+        sub_categories = excluded.sub_categories,
+        image_url = excluded.image_url,
+        parent_id = excluded.parent_id,
+        local = excluded.local",
+        &ids,
+        &names,
+        &sub_categories,
+        &image_urls as &[Option<String>],
+        &parent_ids as &[Option<String>],
+        &locals,
+        &ap_ids,
+    )
+    .execute(&mut *conn)
+    .instrument(debug_span!("pg.insert"))
+    .await
+    .map_err(map_err)?;
+
+    Ok(())
+}
+
+/// Stores the hierarchy worked out for the batch: the parent of every category
+/// it moved, then the sub-categories of every parent that gained or lost one.
+/// Children new to a parent are appended in batch `order`. Returns the
+/// categories whose stored parent or sub-categories changed, as they were before
+async fn sync_hierarchy(
+    conn: &mut PgConnection,
+    parents: &HashMap<String, Option<String>>,
+    touched: &HashSet<String>,
+    affected_parents: &HashSet<String>,
+    order: &[String],
+) -> Result<Vec<Changed>, Status> {
+    let (children, new_parents): (Vec<_>, Vec<_>) = touched
+        .iter()
+        .map(|child| (child.clone(), parents.get(child).cloned().flatten()))
+        .unzip();
+
+    let mut changed = sqlx::query_as!(
+        Changed,
+        "
+        update category c set parent_id = v.parent_id
+        from unnest($1::varchar[], $2::varchar[]) as v (ap_id, parent_id), category old
+        where c.ap_id = v.ap_id and old.ap_id = c.ap_id
+            and c.parent_id is distinct from v.parent_id
+        returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+        &children,
+        &new_parents as &[Option<String>],
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.update.parent"))
+    .await
+    .map_err(map_err)?;
+
+    let affected_parents: Vec<_> = affected_parents.iter().cloned().collect();
+
+    // keeps listed sub-categories in their order and appends new ones
+    let synced = sqlx::query_as!(
+        Changed,
+        "
+        with synced as (
+            select p.ap_id, array(
+                select s.sub
+                from unnest(p.sub_categories) with ordinality as s (sub, ord)
+                where exists (
+                    select 1 from category c
+                    where c.ap_id = s.sub and c.parent_id = p.ap_id and c.deleted_at is null
+                )
+                order by s.ord
+            ) || array(
+                select c.ap_id
+                from category c
+                where c.parent_id = p.ap_id
+                    and c.deleted_at is null
+                    and not c.ap_id = any(p.sub_categories)
+                order by array_position($2, c.ap_id), c.created_at, c.id
+            ) as sub_categories
+            from category p
+            where p.ap_id = any($1) and p.deleted_at is null
+        )
+        update category c set sub_categories = s.sub_categories
+        from synced s, category old
+        where c.ap_id = s.ap_id and old.ap_id = c.ap_id
+            and c.sub_categories is distinct from s.sub_categories
+        returning c.ap_id, to_jsonb(old.*) as \"before!\"",
+        &affected_parents,
+        order
+    )
+    .fetch_all(&mut *conn)
+    .instrument(debug_span!("pg.update.sub_categories"))
+    .await
+    .map_err(map_err)?;
+    changed.extend(synced);
+
+    // a category changed by both statements keeps how it was before the first
+    let mut seen = HashSet::new();
+    changed.retain(|c| seen.insert(c.ap_id.clone()));
+
+    Ok(changed)
+}
+
+async fn record_history(
+    conn: &mut PgConnection,
+    categories: &[&Category],
+    befores: Vec<Option<Value>>,
+    actor: Option<&str>,
+) -> Result<(), Status> {
+    let ids: Vec<_> = categories
+        .iter()
+        .map(|_| utils::generate_id(ID_LENGTH))
+        .collect();
+    let ap_ids: Vec<_> = categories.iter().map(|c| c.ap_id.clone()).collect();
+
+    sqlx::query!(
+        "
+        insert into category_history (id, category_ap_id, operation, actor, before, after)
+        select v.id, v.ap_id, 'upsert', $4, v.before, to_jsonb(c.*)
+        from unnest($1::varchar[], $2::varchar[], $3::jsonb[]) as v (id, ap_id, before)
+        join category c on c.ap_id = v.ap_id",
+        &ids,
+        &ap_ids,
+        &befores as &[Option<Value>],
+        actor,
+    )
+    .execute(conn)
+    .instrument(debug_span!("pg.insert.history"))
+    .await
+    .map_err(map_err)?;
+
+    Ok(())
+}
@@ -1,23 +1,46 @@
-use std::{collections::HashMap, error::Error};
+mod keyset;
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    pin::Pin,
+};
 
 use sellershut_core::{
     categories::{
-        Category, CategoryDetailed, Connection, GetCategoryByIdRequest, GetCategoryByIdResponse,
-        GetCategoryRequest, GetCategoryResponse, GetSubCategoriesRequest, Node, SubCategory,
+        AutocompleteRequest, AutocompleteResponse, AutocompleteSuggestion, Breadcrumb, Category,
+        CategoryChange, CategoryDetailed, CategoryHistoryEntry, Connection, Descendant,
+        GetAncestorsRequest, GetAncestorsResponse, GetCategoriesByApIdsRequest,
+        GetCategoriesByApIdsResponse, GetCategoriesByIdsRequest, GetCategoriesByIdsResponse,
+        GetCategoriesRequest, GetCategoryByExternalIdRequest, GetCategoryByIdRequest,
+        GetCategoryByIdResponse, GetCategoryHistoryRequest, GetCategoryRequest,
+        GetCategoryResponse, GetDescendantsRequest, GetDescendantsResponse,
+        GetSubCategoriesRequest, HistoryConnection, HistoryNode, Node, Origin, OriginScope,
+        SearchCategoriesRequest, SortDirection, SubCategory, WatchCategoriesRequest, origin,
         query_categories_server::QueryCategories,
     },
     common::pagination::{
-        self, Cursor, CursorBuilder, PageInfo,
+        self, CursorBuilder, PageInfo,
         cursor::{Index, cursor_value::CursorType},
     },
 };
-use time::{OffsetDateTime, UtcOffset, format_description::well_known::Rfc3339};
+use sqlx::{Postgres, QueryBuilder};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::{Stream, wrappers::ReceiverStream};
 use tonic::{Request, Response, Status};
 use tracing::{Instrument, debug, debug_span};
 
-use crate::entity;
+use crate::{entity, watch::WatchEvent};
 
 use super::AppState;
+use keyset::{Keyset, SortKey};
+
+/// How many suggestions autocomplete returns unless asked for a number
+const DEFAULT_SUGGESTIONS: i32 = 10;
+
+/// How many names with a later word starting with the prefix autocomplete
+/// ranks, so a prefix few names start with stays quick
+const WORD_CANDIDATES: i64 = 500;
 
 #[tonic::async_trait]
 impl QueryCategories for AppState {
@@ -25,105 +48,163 @@ impl QueryCategories for AppState {
     #[tracing::instrument(skip(self), err(Debug))]
     async fn categories(
         &self,
-        request: Request<pagination::Cursor>,
+        request: Request<GetCategoriesRequest>,
     ) -> Result<Response<Connection>, Status> {
-        let pagination = request.into_inner();
-
-        let max = self.config.max_query_results;
+        let params = request.into_inner();
+        let pagination = params
+            .pagination
+            .ok_or_else(|| Status::invalid_argument("missing pagination"))?;
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let keyset = Keyset::categories(&pagination, params.sort, self.config.max_query_results)?;
 
-        // get count
-        let actual_count = pagination::query_count(
-            max,
-            &pagination.index.ok_or_else(|| {
-                tonic::Status::new(tonic::Code::Internal, "missing pagination index")
-            })?,
-        );
+        let (edges, page_info) = keyset
+            .fetch(
+                &self.services.postgres,
+                |query| origin.push_conditions(query),
+                category_node,
+            )
+            .await?;
 
-        let get_count: i64 = actual_count as i64 + 1;
+        Ok(Response::new(Connection {
+            edges,
+            page_info: Some(page_info),
+        }))
+    }
 
-        // a cursor was provided, so we are skipping to somewhere
-        let connection = if let Some(ref cursor) = pagination.cursor_value {
-            let cursor_value = cursor
-                .cursor_type
-                .as_ref()
-                .expect("cursor value is missing");
+    #[doc = " get category by id"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_by_ap_id(
+        &self,
+        request: tonic::Request<GetCategoryRequest>,
+    ) -> Result<tonic::Response<GetCategoryResponse>, tonic::Status> {
+        let params = request.into_inner();
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let id = params.ap_id;
+        debug!(id = id, "getting by ap_id");
 
-            let cursor = decode_cursor(cursor_value)?;
+        Ok(Response::new(self.category_detailed(&id, &origin).await?))
+    }
 
-            let created_at = OffsetDateTime::parse(cursor.dt(), &Rfc3339).map_err(map_err)?;
+    #[doc = " get the category mapped to an id in an external taxonomy"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn category_by_external_id(
+        &self,
+        request: Request<GetCategoryByExternalIdRequest>,
+    ) -> Result<Response<GetCategoryResponse>, Status> {
+        let params = request.into_inner();
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let external_id = params
+            .external_id
+            .ok_or_else(|| Status::invalid_argument("external_id is required"))?;
+        let taxonomy = entity::taxonomy_name(external_id.taxonomy())
+            .ok_or_else(|| Status::invalid_argument("taxonomy is required"))?;
+        debug!(
+            taxonomy,
+            id = external_id.external_id,
+            "getting by external id"
+        );
 
-            let id = cursor.id();
-            let (count, categories) = match cursor_value {
-                CursorType::After(_cursor) => {
-                    paginate_categories_after(self, &created_at, id, get_count).await?
-                }
-                CursorType::Before(_cursor) => {
-                    paginate_categories_before(self, &created_at, id, get_count).await?
-                }
-            };
+        let ap_id = sqlx::query_scalar!(
+            "
+            select category_ap_id from category_external_id
+            where taxonomy = $1 and external_id = $2",
+            taxonomy,
+            external_id.external_id
+        )
+        .fetch_optional(&self.services.postgres)
+        .instrument(debug_span!("pg.select.external_id"))
+        .await
+        .map_err(map_err)?;
 
-            parse_categories(count, categories, &pagination, actual_count)?
-        } else {
-            let index = match pagination.index.expect("index to be available") {
-                pagination::cursor::Index::First(count) => Index::First(count),
-                pagination::cursor::Index::Last(count) => Index::Last(count),
-            };
-
-            let categories = match index {
-                Index::First(_) => sqlx::query_as!(
-                    entity::Category,
-                    "select * FROM category
-                            where local = $1
-                            order by
-                                created_at asc
-                            limit $2",
-                    true,
-                    get_count,
-                )
-                .fetch_all(&self.services.postgres)
-                .instrument(debug_span!("pg.select.*"))
-                .await
-                .map_err(map_err)?,
-                Index::Last(_) => sqlx::query_as!(
-                    entity::Category,
-                    "select * FROM category
-                            where local = $1
-                            order by
-                                created_at desc
-                            limit $2",
-                    true,
-                    get_count,
-                )
-                .fetch_all(&self.services.postgres)
-                .instrument(debug_span!("pg.select.*"))
-                .await
-                .map_err(map_err)?,
-            };
-
-            parse_categories(
-                Some(get_count - categories.len() as i64),
-                categories,
-                &pagination,
-                actual_count,
-            )?
+        let response = match ap_id {
+            Some(ap_id) => self.category_detailed(&ap_id, &origin).await?,
+            None => GetCategoryResponse::default(),
         };
 
-        Ok(tonic::Response::new(connection))
+        Ok(Response::new(response))
     }
 
-    #[doc = " get category by id"]
+    #[doc = " get a category followed by its ancestors, up to the root"]
     #[tracing::instrument(skip(self), err(Debug))]
-    async fn category_by_ap_id(
+    async fn ancestors(
         &self,
-        request: tonic::Request<GetCategoryRequest>,
-    ) -> Result<tonic::Response<GetCategoryResponse>, tonic::Status> {
-        let id = request.into_inner().ap_id;
-        debug!(id = id, "getting by ap_id");
-        let category = sqlx::query_as!(
-            entity::CategoryDetailed,
-            "select
+        request: Request<GetAncestorsRequest>,
+    ) -> Result<Response<GetAncestorsResponse>, Status> {
+        let params = request.into_inner();
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let id = params.ap_id;
+        debug!(id = id, "getting ancestors");
+        let categories = sqlx::query_as!(
+            entity::Category,
+            "with recursive ancestors (ap_id, parent_id, path) as (
+                select ap_id, parent_id, array[ap_id]
+                from category
+                where ap_id = $1
+                    and ($2::bool is null or local = $2)
+                    and ($3::text is null or category_host(ap_id) = $3)
+                    and deleted_at is null
+                union all
+                select c.ap_id, c.parent_id, a.path || c.ap_id
+                from category c
+                join ancestors a on c.ap_id = a.parent_id
+                where not c.ap_id = any(a.path)
+                    and ($2::bool is null or c.local = $2)
+                    and ($3::text is null or category_host(c.ap_id) = $3)
+            )
+            select c.* from ancestors a
+            join category c on c.ap_id = a.ap_id
+            order by cardinality(a.path)",
+            id,
+            origin.local,
+            origin.host
+        )
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.ancestors"))
+        .await
+        .map_err(map_err)?;
+
+        Ok(Response::new(GetAncestorsResponse {
+            categories: categories.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    #[doc = " get every category beneath a category, down to an optional depth"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn descendants(
+        &self,
+        request: Request<GetDescendantsRequest>,
+    ) -> Result<Response<GetDescendantsResponse>, Status> {
+        let params = request.into_inner();
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        debug!(id = params.ap_id, "getting descendants");
+
+        if params.max_depth.is_some_and(|depth| depth < 1) {
+            return Err(Status::invalid_argument("max_depth must be at least 1"));
+        }
+
+        let categories = sqlx::query_as!(
+            entity::CategoryDescendant,
+            "with recursive descendants (ap_id, depth, path) as (
+                select ap_id, 0, array[ap_id]
+                from category
+                where ap_id = $1
+                    and ($2::bool is null or local = $2)
+                    and ($3::text is null or category_host(ap_id) = $3)
+                    and deleted_at is null
+                union all
+                select c.ap_id, d.depth + 1, d.path || c.ap_id
+                from category c
+                join descendants d on c.parent_id = d.ap_id
+                where ($4::int is null or d.depth < $4)
+                    and ($2::bool is null or c.local = $2)
+                    and ($3::text is null or category_host(c.ap_id) = $3)
+                    and c.deleted_at is null
+                    and not c.ap_id = any(d.path)
+            )
+            select
                 c.id as id,
                 c.name as name,
+                d.depth as \"depth!\",
                 c.image_url as image_url,
                 c.ap_id as ap_id,
                 c.local as local,
@@ -132,24 +213,31 @@ impl QueryCategories for AppState {
                 c.updated_at as updated_at,
                 subcategory.ap_id AS \"sub_category_ap_id?\",
                 subcategory.name AS \"sub_category_name?\"
-            from category c
+            from descendants d
+            join category c on c.ap_id = d.ap_id
             left join lateral (
                 select ap_id, name
                 from category sub
                 where sub.ap_id = any(c.sub_categories)
+                    and ($2::bool is null or sub.local = $2)
+                    and ($3::text is null or category_host(sub.ap_id) = $3)
+                    and sub.deleted_at is null
             ) as subcategory on true
-                where c.ap_id = $1 and local = $2",
-            id,
-            true
+            where d.depth > 0
+            order by d.depth, c.created_at, c.id",
+            params.ap_id,
+            origin.local,
+            origin.host,
+            params.max_depth,
         )
         .fetch_all(&self.services.postgres)
-        .instrument(debug_span!("pg.select.*"))
+        .instrument(debug_span!("pg.select.descendants"))
         .await
         .map_err(map_err)?;
 
-        let category = prepare_single_category(category).ok();
-
-        Ok(tonic::Response::new(GetCategoryResponse { category }))
+        Ok(Response::new(GetDescendantsResponse {
+            descendants: prepare_descendants(categories),
+        }))
     }
 
     #[doc = " get category by id"]
@@ -158,14 +246,22 @@ impl QueryCategories for AppState {
         &self,
         request: tonic::Request<GetCategoryByIdRequest>,
     ) -> Result<tonic::Response<GetCategoryByIdResponse>, tonic::Status> {
-        let id = request.into_inner().id;
+        let params = request.into_inner();
+        // ids are given out by this instance, so they find any category by default
+        let origin = OriginFilter::new(params.origin, OriginScope::All)?;
+        let id = params.id;
         debug!(id = id, "getting by id");
         let category = sqlx::query_as!(
             entity::Category,
             "select * from category
             where id = $1
+                and ($2::bool is null or local = $2)
+                and ($3::text is null or category_host(ap_id) = $3)
+                and deleted_at is null
             ",
-            id
+            id,
+            origin.local,
+            origin.host
         )
         .fetch_optional(&self.services.postgres)
         .instrument(debug_span!("pg.select.*"))
@@ -177,326 +273,540 @@ impl QueryCategories for AppState {
         }))
     }
 
-    #[doc = " get subcategories"]
+    #[doc = " get the categories with any of the given ids, in the order they were asked for"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn categories_by_ids(
+        &self,
+        request: Request<GetCategoriesByIdsRequest>,
+    ) -> Result<Response<GetCategoriesByIdsResponse>, Status> {
+        let params = request.into_inner();
+        let origin = OriginFilter::new(params.origin, OriginScope::All)?;
+        let ids = dedup_keys(params.ids, self.config.max_query_results)?;
+        debug!(count = ids.len(), "getting by ids");
+
+        let categories = sqlx::query_as!(
+            entity::Category,
+            "select * from category
+            where id = any($1)
+                and ($2::bool is null or local = $2)
+                and ($3::text is null or category_host(ap_id) = $3)
+                and deleted_at is null",
+            &ids,
+            origin.local,
+            origin.host
+        )
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?;
+
+        let (categories, missing) = match_keys(ids, categories, |category| &category.id);
+
+        Ok(Response::new(GetCategoriesByIdsResponse {
+            categories,
+            missing,
+        }))
+    }
+
+    #[doc = " get the categories with any of the given ap_ids, in the order they were asked for"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn categories_by_ap_ids(
+        &self,
+        request: Request<GetCategoriesByApIdsRequest>,
+    ) -> Result<Response<GetCategoriesByApIdsResponse>, Status> {
+        let params = request.into_inner();
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let ap_ids = dedup_keys(params.ap_ids, self.config.max_query_results)?;
+        debug!(count = ap_ids.len(), "getting by ap_ids");
+
+        let categories = sqlx::query_as!(
+            entity::Category,
+            "select * from category
+            where ap_id = any($1)
+                and ($2::bool is null or local = $2)
+                and ($3::text is null or category_host(ap_id) = $3)
+                and deleted_at is null",
+            &ap_ids,
+            origin.local,
+            origin.host
+        )
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?;
+
+        let (categories, missing) = match_keys(ap_ids, categories, |category| &category.ap_id);
+
+        Ok(Response::new(GetCategoriesByApIdsResponse {
+            categories,
+            missing,
+        }))
+    }
+
+    #[doc = " get the direct sub-categories of a category, or top-level categories without one"]
     #[tracing::instrument(skip(self), err(Debug))]
     async fn sub_categories(
         &self,
         request: Request<GetSubCategoriesRequest>,
     ) -> Result<Response<Connection>, Status> {
         let params = request.into_inner();
-        let pagination = params.pagination.expect("pagination params");
+        let pagination = params
+            .pagination
+            .ok_or_else(|| Status::invalid_argument("missing pagination"))?;
         let parent_id = params.id;
+        let include_roots = params.include_roots;
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let keyset = Keyset::categories(&pagination, params.sort, self.config.max_query_results)?;
+
+        let (edges, page_info) = keyset
+            .fetch(
+                &self.services.postgres,
+                |query| {
+                    match parent_id.as_ref() {
+                        Some(parent_id) => {
+                            query
+                                .push(" and (parent_id = ")
+                                .push_bind(parent_id.clone());
+                            if include_roots {
+                                query.push(" or parent_id is null");
+                            }
+                            query.push(")");
+                        }
+                        None => {
+                            query.push(" and parent_id is null");
+                        }
+                    }
+                    origin.push_conditions(query);
+                },
+                category_node,
+            )
+            .await?;
+
+        Ok(Response::new(Connection {
+            edges,
+            page_info: Some(page_info),
+        }))
+    }
 
-        let max = self.config.max_query_results;
+    #[doc = " search category names, most relevant first"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn search(
+        &self,
+        request: Request<SearchCategoriesRequest>,
+    ) -> Result<Response<Connection>, Status> {
+        let params = request.into_inner();
+        let query = params.query.trim();
+        if query.is_empty() {
+            return Err(Status::invalid_argument("query is required"));
+        }
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        let pagination = params
+            .pagination
+            .ok_or_else(|| Status::invalid_argument("missing pagination"))?;
+        debug!(query, "searching categories");
 
-        // get count
+        // relevance only makes sense in one direction
+        let Some(Index::First(first)) = pagination.index else {
+            return Err(Status::invalid_argument(
+                "search results are paged with first and after",
+            ));
+        };
         let actual_count = pagination::query_count(
-            max,
-            &pagination.index.ok_or_else(|| {
-                tonic::Status::new(tonic::Code::Internal, "missing pagination index")
-            })?,
+            self.config.max_query_results,
+            &pagination::cursor::Index::First(first),
         );
-
         let get_count: i64 = actual_count as i64 + 1;
-        dbg!(get_count);
-
-        // a cursor was provided, so we are skipping to somewhere
-        let connection = if let Some(ref cursor) = pagination.cursor_value {
-            let cursor_value = cursor
-                .cursor_type
-                .as_ref()
-                .expect("cursor value is missing");
-
-            let cursor = decode_cursor(cursor_value)?;
-
-            let created_at = OffsetDateTime::parse(cursor.dt(), &Rfc3339).map_err(map_err)?;
-
-            let id = cursor.id();
-            let (count, categories) = match cursor_value {
-                CursorType::After(_cursor) => {
-                    paginate_sub_categories_after(
-                        self,
-                        &created_at,
-                        id,
-                        get_count,
-                        parent_id.as_deref(),
-                    )
-                    .await?
+
+        // the cursor carries the rank, and the query it ranks against, where a
+        // timestamp would usually be
+        let after = match pagination.cursor_value.and_then(|value| value.cursor_type) {
+            None => None,
+            Some(CursorType::Before(_)) => {
+                return Err(Status::invalid_argument(
+                    "search results are paged with first and after",
+                ));
+            }
+            Some(cursor_value) => {
+                let cursor = decode_cursor(&cursor_value)?;
+                let (rank, cursor_query) = cursor
+                    .dt()
+                    .split_once(':')
+                    .ok_or_else(|| Status::invalid_argument("cursor is not from a search"))?;
+                if cursor_query != query {
+                    return Err(Status::invalid_argument(
+                        "cursor is from a search for something else",
+                    ));
                 }
-                CursorType::Before(_cursor) => {
-                    paginate_sub_categories_before(
-                        self,
-                        &created_at,
-                        id,
-                        get_count,
-                        parent_id.as_deref(),
+                let rank = rank
+                    .parse::<f32>()
+                    .map_err(|_| Status::invalid_argument("cursor is not from a search"))?;
+                Some((rank, cursor.id().to_string()))
+            }
+        };
+
+        let (after_rank, after_id) = after.clone().unzip();
+
+        let matches = sqlx::query_as!(
+            entity::CategoryMatch,
+            "
+            select
+                id as \"id!\",
+                name as \"name!\",
+                sub_categories as \"sub_categories!\",
+                image_url,
+                parent_id,
+                created_at as \"created_at!\",
+                updated_at as \"updated_at!\",
+                ap_id as \"ap_id!\",
+                local as \"local!\",
+                rank as \"rank!\"
+            from (
+                select c.*, (
+                    ts_rank(to_tsvector('english', c.name), websearch_to_tsquery('english', $1))
+                    + word_similarity($1, c.name)
+                )::real as rank
+                from category c
+                where ($2::bool is null or c.local = $2)
+                    and ($3::text is null or category_host(c.ap_id) = $3)
+                    and c.deleted_at is null
+                    and (
+                        to_tsvector('english', c.name) @@ websearch_to_tsquery('english', $1)
+                        or c.name % $1
+                        or $1 <% c.name
                     )
-                    .await?
-                }
-            };
+            ) matches
+            where $4::real is null or rank < $4 or (rank = $4 and id > $5)
+            order by rank desc, id
+            limit $6",
+            query,
+            origin.local,
+            origin.host,
+            after_rank,
+            after_id,
+            get_count
+        )
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.search"))
+        .await
+        .map_err(map_err)?;
 
-            parse_categories(count, categories, &pagination, actual_count)?
-        } else {
-            let index = match pagination.index.expect("index to be available") {
-                pagination::cursor::Index::First(count) => Index::First(count),
-                pagination::cursor::Index::Last(count) => Index::Last(count),
-            };
-
-            let categories = match index {
-                Index::First(_) => sqlx::query_as!(
-                    entity::Category,
-                    "select * FROM category
-                        where 
-                            (($2::text is not null and parent_id = $2) or parent_id is null)
-                            and local = $3
-                        order by
-                            created_at asc
-                        limit $1",
-                    get_count,
-                    parent_id,
-                    true
-                )
-                .fetch_all(&self.services.postgres)
-                .instrument(debug_span!("pg.select.count"))
-                .await
-                .map_err(map_err)?,
-                Index::Last(_) => sqlx::query_as!(
-                    entity::Category,
-                    "select * FROM category
-                        where
-                            (($2::text is not null and parent_id = $2) or parent_id is null)
-                             and local = $3
-                        order by
-                            created_at desc
-                        limit $1",
-                    get_count,
-                    parent_id,
-                    true
+        let has_next_page = matches.len() > actual_count as usize;
+        let edges: Vec<_> = matches
+            .into_iter()
+            .take(actual_count as usize)
+            .map(|category| Node {
+                cursor: CursorBuilder::new(&category.id, &format!("{}:{query}", category.rank))
+                    .encode(),
+                node: Some(category.into()),
+            })
+            .collect();
+
+        let page_info = PageInfo {
+            has_next_page,
+            has_previous_page: after.is_some(),
+            start_cursor: edges.first().map(|edge| edge.cursor.clone()),
+            end_cursor: edges.last().map(|edge| edge.cursor.clone()),
+        };
+
+        Ok(Response::new(Connection {
+            edges,
+            page_info: Some(page_info),
+        }))
+    }
+
+    #[doc = " suggest categories whose name, or a word in it, starts with a prefix"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn autocomplete(
+        &self,
+        request: Request<AutocompleteRequest>,
+    ) -> Result<Response<AutocompleteResponse>, Status> {
+        let params = request.into_inner();
+        let prefix = params.prefix.trim().to_lowercase();
+        if prefix.is_empty() {
+            return Err(Status::invalid_argument("prefix is required"));
+        }
+        let limit = match params.limit {
+            None => DEFAULT_SUGGESTIONS,
+            Some(limit) if limit > 0 => limit.min(self.config.max_query_results),
+            Some(_) => return Err(Status::invalid_argument("limit must be positive")),
+        };
+        let origin = OriginFilter::new(params.origin, OriginScope::Local)?;
+        debug!(prefix, limit, "autocompleting categories");
+
+        let pattern = format!("{}%", escape_like(&prefix));
+        let words = prefix_tsquery(&prefix);
+
+        // names starting with the prefix come before those with a later word
+        // starting with it, then shallower and more often picked categories.
+        // a short prefix matches so many names that they are walked in rank
+        // order off an index until there are enough, while a longer one is
+        // looked up by name and sorted. which is quicker depends on the prefix,
+        // so the statement is planned afresh each time rather than cached
+        let mut categories = sqlx::query_as::<_, entity::Category>(
+            "
+            select c.* from category_rank r
+            join category c on c.ap_id = r.category_ap_id
+            where lower(r.name) like $1
+                and ($2::bool is null or c.local = $2)
+                and ($3::text is null or category_host(c.ap_id) = $3)
+                and c.deleted_at is null
+            order by r.depth, r.popularity desc, r.name, r.category_ap_id
+            limit $4",
+        )
+        .bind(&pattern)
+        .bind(origin.local)
+        .bind(origin.host.as_deref())
+        .bind(i64::from(limit))
+        .persistent(false)
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.autocomplete"))
+        .await
+        .map_err(map_err)?;
+
+        // later words are only reached when few names start with the prefix,
+        // and only the first WORD_CANDIDATES of them are ranked
+        let remaining = i64::from(limit) - categories.len() as i64;
+        if let Some(words) = words.filter(|_| remaining > 0) {
+            categories.extend(
+                sqlx::query_as::<_, entity::Category>(
+                    "
+                    with candidates as materialized (
+                        select category_ap_id, depth, popularity, name
+                        from category_rank
+                        where to_tsvector('simple', name) @@ to_tsquery('simple', $2)
+                            and not lower(name) like $1
+                        limit $6
+                    )
+                    select c.* from (
+                        select * from candidates
+                        order by depth, popularity desc, name, category_ap_id
+                    ) r
+                    cross join lateral (
+                        select * from category c
+                        where c.ap_id = r.category_ap_id
+                            and ($3::bool is null or c.local = $3)
+                            and ($4::text is null or category_host(c.ap_id) = $4)
+                            and c.deleted_at is null
+                    ) c
+                    order by r.depth, r.popularity desc, r.name, r.category_ap_id
+                    limit $5",
                 )
+                .bind(&pattern)
+                .bind(words)
+                .bind(origin.local)
+                .bind(origin.host.as_deref())
+                .bind(remaining)
+                .bind(WORD_CANDIDATES)
                 .fetch_all(&self.services.postgres)
-                .instrument(debug_span!("pg.select.*"))
+                .instrument(debug_span!("pg.select.autocomplete"))
                 .await
                 .map_err(map_err)?,
-            };
-
-            parse_categories(
-                Some(get_count - categories.len() as i64),
-                categories,
-                &pagination,
-                actual_count,
-            )?
-        };
+            );
+        }
+
+        let ap_ids: Vec<_> = categories.iter().map(|c| c.ap_id.clone()).collect();
+
+        let mut breadcrumbs: HashMap<String, Vec<Breadcrumb>> = HashMap::new();
+        let ancestors = sqlx::query!(
+            "
+            with recursive ancestors (category_ap_id, ap_id, name, parent_id, path) as (
+                select c.ap_id, p.ap_id, p.name, p.parent_id, array[c.ap_id, p.ap_id]
+                from category c
+                join category p on p.ap_id = c.parent_id
+                where c.ap_id = any($1)
+                union all
+                select a.category_ap_id, p.ap_id, p.name, p.parent_id, a.path || p.ap_id
+                from ancestors a
+                join category p on p.ap_id = a.parent_id
+                where not p.ap_id = any(a.path)
+            )
+            select
+                category_ap_id as \"category_ap_id!\",
+                ap_id as \"ap_id!\",
+                name as \"name!\"
+            from ancestors
+            order by category_ap_id, cardinality(path) desc",
+            &ap_ids
+        )
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.ancestors"))
+        .await
+        .map_err(map_err)?;
 
-        Ok(tonic::Response::new(connection))
+        for ancestor in ancestors {
+            breadcrumbs
+                .entry(ancestor.category_ap_id)
+                .or_default()
+                .push(Breadcrumb {
+                    ap_id: ancestor.ap_id,
+                    name: ancestor.name,
+                });
+        }
+
+        let suggestions = categories
+            .into_iter()
+            .map(|category| AutocompleteSuggestion {
+                breadcrumbs: breadcrumbs.remove(&category.ap_id).unwrap_or_default(),
+                category: Some(category.into()),
+            })
+            .collect();
+
+        Ok(Response::new(AutocompleteResponse { suggestions }))
     }
-}
 
-async fn paginate_sub_categories_before(
-    state: &AppState,
-    created_at: &OffsetDateTime,
-    id: &str,
-    get_count: i64,
-    parent_id: Option<&str>,
-) -> Result<(Option<i64>, Vec<entity::Category>), tonic::Status> {
-    let fut_count = sqlx::query_scalar!(
-        "
-            select count(*) from category
-            where 
-                (((
-                    created_at <> $1
-                    or id > $2
-                )
-                and created_at >= $1) and (($3::text is not null and parent_id = $3) or parent_id is null)) and local = $4
-        ",
-        created_at,
-        id,
-        parent_id,
-        true
-    )
-    .fetch_one(&state.services.postgres)
-    .instrument(debug_span!("pg.select.count"));
-
-    let fut_categories = sqlx::query_as!(
-        entity::Category,
-        "
-            select * from category
-            where 
-                (((
-                    created_at = $1
-                    and id < $2
-                )
-                or created_at < $1) and (($4::text is not null and parent_id = $4) or parent_id is null)) and local = $5
-            order by
-                created_at desc,
-                id desc
-            limit
-                $3
-        ",
-        created_at,
-        id,
-        get_count,
-        parent_id,
-        true
-    )
-    .fetch_all(&state.services.postgres)
-    .instrument(debug_span!("pg.select.*"));
-
-    tokio::try_join!(fut_count, fut_categories).map_err(map_err)
+    #[doc = " get the changes made to a category, oldest first"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn history(
+        &self,
+        request: Request<GetCategoryHistoryRequest>,
+    ) -> Result<Response<HistoryConnection>, Status> {
+        let params = request.into_inner();
+        let pagination = params
+            .pagination
+            .ok_or_else(|| Status::invalid_argument("missing pagination"))?;
+        let ap_id = params.ap_id;
+        debug!(id = ap_id, "getting history");
+
+        let keyset = Keyset::new(
+            &pagination,
+            SortKey::Timestamp("created_at", |entry: &entity::CategoryHistory| {
+                entry.created_at
+            }),
+            SortDirection::Asc,
+            self.config.max_query_results,
+        )?;
+
+        let (edges, page_info) = keyset
+            .fetch(
+                &self.services.postgres,
+                |query| {
+                    query
+                        .push(" and category_ap_id = ")
+                        .push_bind(ap_id.clone());
+                },
+                |entry, cursor| {
+                    Ok(HistoryNode {
+                        node: Some(CategoryHistoryEntry::try_from(entry).map_err(map_err)?),
+                        cursor,
+                    })
+                },
+            )
+            .await?;
+
+        Ok(Response::new(HistoryConnection {
+            edges,
+            page_info: Some(page_info),
+        }))
+    }
+
+    #[doc = "Server streaming response type for the Watch method."]
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<CategoryChange, Status>> + Send>>;
+
+    #[doc = " stream changes to categories as they are committed, optionally only"]
+    #[doc = " within a category's subtree"]
+    #[tracing::instrument(skip(self), err(Debug))]
+    async fn watch(
+        &self,
+        request: Request<WatchCategoriesRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let root = request.into_inner().ap_id;
+        debug!(id = root, "watching categories");
+
+        // subscribe before responding so no change after this call is missed
+        let mut changes = self.changes.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = tx.closed() => break,
+                    event = changes.recv() => event,
+                };
+
+                let changed = match event {
+                    Ok(WatchEvent::Changed(changed)) => changed,
+                    Ok(WatchEvent::Interrupted) => {
+                        let msg = "changes may have been missed while the listener reconnected";
+                        let _ = tx.send(Err(Status::data_loss(msg))).await;
+                        break;
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        let msg = format!("watcher fell behind and missed {missed} changes");
+                        let _ = tx.send(Err(Status::data_loss(msg))).await;
+                        break;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+
+                if !changed.in_subtree(root.as_deref()) {
+                    continue;
+                }
+
+                if tx.send(Ok(changed.to_change())).await.is_err() {
+                    break;
+                }
+            }
+
+            debug!(id = root, "stopped watching categories");
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
 }
 
-async fn paginate_sub_categories_after(
-    state: &AppState,
-    created_at: &OffsetDateTime,
-    id: &str,
-    get_count: i64,
-    parent_id: Option<&str>,
-) -> Result<(Option<i64>, Vec<entity::Category>), tonic::Status> {
-    let fut_count = sqlx::query_scalar!(
-        "
-            select count(*) from category
-            where 
-                (((
-                    created_at <> $1
-                    or id <= $2
-                )
-                and created_at < $1) and (($3::text is not null and parent_id = $3) or parent_id is null)) and local = $4
-        ",
-        created_at,
-        id,
-        parent_id,
-        true
-    )
-    .fetch_one(&state.services.postgres)
-    .instrument(debug_span!("pg.select.count"));
-
-    let fut_categories = sqlx::query_as!(
-        entity::Category,
-        "
-            select * from category
-            where 
-                (((
-                    created_at = $1
-                    and id > $2
-                )
-                or created_at > $1) and (($4::text is not null and parent_id = $4) or parent_id is null)) and local = $5
-            order by
-                created_at asc,
-                id asc
-            limit
-                $3
-        ",
-        created_at,
-        id,
-        get_count,
-        parent_id,
-        true
-    )
-    .fetch_all(&state.services.postgres)
-    .instrument(debug_span!("pg.select.*"));
-
-    tokio::try_join!(fut_count, fut_categories).map_err(map_err)
+/// Drops repeated keys, keeping the order they were given in
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+fn dedup_keys(keys: Vec<String>, max: i32) -> Result<Vec<String>, Status> {
+    let mut seen = HashSet::with_capacity(keys.len());
+    let keys: Vec<_> = keys
+        .into_iter()
+        .filter(|key| seen.insert(key.clone()))
+        .collect();
+
+    if keys.len() > max as usize {
+        return Err(Status::invalid_argument(format!(
+            "at most {max} categories can be requested at once"
+        )));
+    }
+
+    Ok(keys)
 }
 
-async fn paginate_categories_before(
-    state: &AppState,
-    created_at: &OffsetDateTime,
-    id: &str,
-    get_count: i64,
-) -> Result<(Option<i64>, Vec<entity::Category>), tonic::Status> {
-    let fut_count = sqlx::query_scalar!(
-        "
-            select count(*) from category
-            where 
-                ((
-                    created_at <> $1
-                    or id > $2
-                )
-                and created_at >= $1) and local = $3
-        ",
-        created_at,
-        id,
-        true
-    )
-    .fetch_one(&state.services.postgres)
-    .instrument(debug_span!("pg.select.count"));
-
-    let fut_categories = sqlx::query_as!(
-        entity::Category,
-        "
-            select * from category
-            where 
-                ((
-                    created_at = $1
-                    and id < $2
-                )
-                or created_at < $1) and local = $4
-            order by
-                created_at desc,
-                id desc
-            limit
-                $3
-        ",
-        created_at,
-        id,
-        get_count,
-        true
-    )
-    .fetch_all(&state.services.postgres)
-    .instrument(debug_span!("pg.select.*"));
-
-    tokio::try_join!(fut_count, fut_categories).map_err(map_err)
+/// Orders `categories` like `keys`, and lists the keys nothing was found for
+fn match_keys(
+    keys: Vec<String>,
+    categories: Vec<entity::Category>,
+    key: impl Fn(&entity::Category) -> &String,
+) -> (Vec<Category>, Vec<String>) {
+    let mut found: HashMap<_, _> = categories
+        .into_iter()
+        .map(|category| (key(&category).clone(), category))
+        .collect();
+
+    let mut missing = Vec::new();
+    let categories = keys
+        .into_iter()
+        .filter_map(|k| {
+            let category = found.remove(&k);
+            if category.is_none() {
+                missing.push(k);
+            }
+            category.map(Into::into)
+        })
+        .collect();
+
+    (categories, missing)
 }
 
-async fn paginate_categories_after(
-    state: &AppState,
-    created_at: &OffsetDateTime,
-    id: &str,
-    get_count: i64,
-) -> Result<(Option<i64>, Vec<entity::Category>), tonic::Status> {
-    let fut_count = sqlx::query_scalar!(
-        "
-            select count(*) from category
-            where 
-                ((
-                    created_at <> $1
-                    or id <= $2
-                )
-                and created_at < $1) and local = $3
-        ",
-        created_at,
-        id,
-        true
-    )
-    .fetch_one(&state.services.postgres)
-    .instrument(debug_span!("pg.select.count"));
-
-    let fut_categories = sqlx::query_as!(
-        entity::Category,
-        "
-            select * from category
-            where 
-                ((
-                    created_at = $1
-                    and id > $2
-                )
-                or created_at > $1) and local = $4
-            order by
-                created_at asc,
-                id asc
-            limit
-                $3
-        ",
-        created_at,
-        id,
-        get_count,
-        true
-    )
-    .fetch_all(&state.services.postgres)
-    .instrument(debug_span!("pg.select.*"));
-
-    tokio::try_join!(fut_count, fut_categories).map_err(map_err)
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+fn category_node(category: entity::Category, cursor: String) -> Result<Node, Status> {
+    Ok(Node {
+        cursor,
+        node: Some(category.into()),
+    })
 }
 
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
 fn decode_cursor(cursor_value: &CursorType) -> Result<CursorBuilder, Status> {
     CursorBuilder::decode(cursor_value).map_err(|e| tonic::Status::internal(e.to_string()))
 }
@@ -505,94 +815,146 @@ fn map_err(err: impl Error) -> tonic::Status {
     tonic::Status::new(tonic::Code::Internal, err.to_string())
 }
 
-fn parse_categories(
-    count_on_other_end: Option<i64>,
-    categories: Vec<entity::Category>,
-    pagination: &Cursor,
-    actual_count: i32,
-) -> Result<Connection, tonic::Status> {
-    let user_count = actual_count as usize;
+/// Which categories a query matches by where they come from
+#[derive(Debug, Clone, Default)]
+struct OriginFilter {
+    /// Only local or only remote categories
+    local: Option<bool>,
+    /// Only categories whose `ap_id` is on this host
+    host: Option<String>,
+}
 
-    let count_on_other_end = count_on_other_end
-        .ok_or_else(|| tonic::Status::new(tonic::Code::Internal, "count returned no items"))?;
-    let left_side = CursorBuilder::is_paginating_from_left(pagination);
+impl OriginFilter {
+    /// `default` applies when the request gives no origin
+    #[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+    fn new(origin: Option<Origin>, default: OriginScope) -> Result<Self, Status> {
+        let scope = match origin.and_then(|origin| origin.filter) {
+            None => default,
+            Some(origin::Filter::Scope(scope)) => OriginScope::try_from(scope)
+                .map_err(|_| Status::invalid_argument("origin scope is invalid"))?,
+            Some(origin::Filter::Host(host)) => {
+                let host = host.trim().to_lowercase();
+                if host.is_empty() || host.contains(['/', ':', '@']) {
+                    return Err(Status::invalid_argument(
+                        "origin host must be a host name, e.g. example.com",
+                    ));
+                }
+                return Ok(Self {
+                    local: None,
+                    host: Some(host),
+                });
+            }
+        };
 
-    let len = categories.len();
+        let local = match scope {
+            OriginScope::Local => Some(true),
+            OriginScope::Remote => Some(false),
+            OriginScope::All => None,
+        };
 
-    let has_more = len > user_count;
+        Ok(Self { local, host: None })
+    }
 
-    let to_node = |category: entity::Category| -> Result<Node, tonic::Status> {
-        let category = Category::from(category);
+    /// Appends the filter to a query's conditions
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(local) = self.local {
+            query.push(" and local = ").push_bind(local);
+        }
+        if let Some(host) = self.host.clone() {
+            query.push(" and category_host(ap_id) = ").push_bind(host);
+        }
+    }
+}
 
-        let dt = category.created_at.expect("to exist");
-        let dt = OffsetDateTime::try_from(dt)
-            .map_err(|_| tonic::Status::invalid_argument("timestamp is invalid"))?;
+/// Escapes the characters `like` treats specially
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
 
-        dt.to_offset(UtcOffset::UTC)
-            .format(&Rfc3339)
-            .map(|dt| {
-                let cursor = CursorBuilder::new(&category.id, &dt);
-                Node {
-                    node: Some(category),
-                    cursor: cursor.encode(),
-                }
-            })
-            .map_err(map_err)
-    };
+/// A `tsquery` matching names with a word starting with each word of the
+/// prefix, or `None` if the prefix has no words
+fn prefix_tsquery(prefix: &str) -> Option<String> {
+    let words: Vec<_> = prefix
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{word}:*"))
+        .collect();
 
-    dbg!(&categories);
+    (!words.is_empty()).then(|| words.join(" & "))
+}
 
-    let categories: Result<Vec<_>, _> = if left_side {
-        categories
-            .into_iter()
-            .take(user_count)
-            .map(&to_node)
-            .collect()
-    } else if has_more {
-        categories
+impl AppState {
+    /// A category with its sub-categories and external ids
+    async fn category_detailed(
+        &self,
+        ap_id: &str,
+        origin: &OriginFilter,
+    ) -> Result<GetCategoryResponse, Status> {
+        let category = sqlx::query_as!(
+            entity::CategoryDetailed,
+            "select
+                c.id as id,
+                c.name as name,
+                c.image_url as image_url,
+                c.ap_id as ap_id,
+                c.local as local,
+                c.created_at as created_at,
+                c.parent_id as parent_id,
+                c.updated_at as updated_at,
+                subcategory.ap_id AS \"sub_category_ap_id?\",
+                subcategory.name AS \"sub_category_name?\"
+            from category c
+            left join lateral (
+                select ap_id, name
+                from category sub
+                where sub.ap_id = any(c.sub_categories) and sub.deleted_at is null
+            ) as subcategory on true
+                where c.ap_id = $1
+                    and ($2::bool is null or c.local = $2)
+                    and ($3::text is null or category_host(c.ap_id) = $3)
+                    and c.deleted_at is null",
+            ap_id,
+            origin.local,
+            origin.host
+        )
+        .fetch_all(&self.services.postgres)
+        .instrument(debug_span!("pg.select.*"))
+        .await
+        .map_err(map_err)?;
+
+        let category = prepare_single_category(category).ok();
+
+        let external_ids = if category.is_none() {
+            Vec::new()
+        } else {
+            sqlx::query_as!(
+                entity::CategoryExternalId,
+                "
+                select taxonomy, external_id from category_external_id
+                where category_ap_id = $1
+                order by taxonomy",
+                ap_id
+            )
+            .fetch_all(&self.services.postgres)
+            .instrument(debug_span!("pg.select.external_id"))
+            .await
+            .map_err(map_err)?
             .into_iter()
-            .rev() // need to take from the right hand side as those
-            // are the last ones
-            .take(user_count)
-            // https://relay.dev/graphql/connections.htm#sel-FAJJDCBEBay8J
-            //  .rev() // restore the order
-            .map(&to_node)
+            .map(Into::into)
             .collect()
-    } else {
-        // restore order from db https://relay.dev/graphql/connections.htm#sel-FAJJDCBEBay8J
-        categories.into_iter().rev().map(&to_node).collect()
-    };
-    dbg!(count_on_other_end);
-
-    let edges = categories?;
-    let start = edges.first().map(|f| f.cursor.clone());
-    let end = edges.last().map(|f| f.cursor.clone());
-
-    let connection = Connection {
-        edges,
-        page_info: Some(PageInfo {
-            has_next_page: {
-                if !left_side {
-                    false
-                } else {
-                    count_on_other_end > 0
-                }
-            },
-            has_previous_page: {
-                if left_side {
-                    false
-                } else {
-                    count_on_other_end > 0
-                }
-            },
-            start_cursor: start,
-            end_cursor: end,
-        }),
-    };
+        };
 
-    Ok(connection)
+        Ok(GetCategoryResponse {
+            category,
+            external_ids,
+        })
+    }
 }
 
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
 pub fn prepare_single_category(
     category: Vec<entity::CategoryDetailed>,
 ) -> Result<CategoryDetailed, Status> {
@@ -629,3 +991,41 @@ pub fn prepare_single_category(
         .map(|(_, category)| category)
         .ok_or_else(|| tonic::Status::internal("query returned none"))
 }
+
+/// Folds the one-row-per-sub-category results of a descendants query, which
+/// are ordered by category, into one entry per category
+fn prepare_descendants(categories: Vec<entity::CategoryDescendant>) -> Vec<Descendant> {
+    let mut descendants: Vec<Descendant> = Vec::with_capacity(categories.len());
+
+    for result in categories {
+        let sub_category = match (result.sub_category_name, result.sub_category_ap_id) {
+            (Some(name), Some(ap_id)) => Some(SubCategory { name, ap_id }),
+            _ => None,
+        };
+
+        match descendants.last_mut() {
+            Some(Descendant {
+                category: Some(category),
+                ..
+            }) if category.ap_id == result.ap_id => {
+                category.sub_categories.extend(sub_category);
+            }
+            _ => descendants.push(Descendant {
+                category: Some(CategoryDetailed {
+                    id: result.id,
+                    name: result.name,
+                    sub_categories: sub_category.into_iter().collect(),
+                    image_url: result.image_url,
+                    parent_id: result.parent_id,
+                    created_at: Some(result.created_at.into()),
+                    updated_at: Some(result.updated_at.into()),
+                    ap_id: result.ap_id,
+                    local: result.local,
+                }),
+                depth: result.depth,
+            }),
+        }
+    }
+
+    descendants
+}
@@ -0,0 +1,287 @@
+use sellershut_core::{
+    categories::{CategorySort, CategorySortKey, SortDirection},
+    common::pagination::{
+        self, Cursor, CursorBuilder, PageInfo,
+        cursor::{Index, cursor_value::CursorType},
+    },
+};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, postgres::PgRow};
+use time::{OffsetDateTime, UtcOffset, format_description::well_known::Rfc3339};
+use tonic::Status;
+use tracing::{Instrument, debug_span};
+
+use crate::entity;
+
+use super::{decode_cursor, map_err};
+
+/// Rows a `Keyset` can page through
+pub(super) trait KeysetRow: for<'r> FromRow<'r, PgRow> + Send + Unpin {
+    /// The table pages are drawn from and the conditions every row meets, as
+    /// `<table> where <conditions>`. Filters append theirs starting with `and`
+    const SOURCE: &'static str;
+
+    /// Breaks ties between rows with the same sort value
+    fn id(&self) -> &str;
+}
+
+impl KeysetRow for entity::Category {
+    const SOURCE: &'static str = "category where deleted_at is null";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl KeysetRow for entity::CategoryHistory {
+    const SOURCE: &'static str = "category_history where true";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// The column rows are ordered by, and how to read it off a row
+pub(super) enum SortKey<T> {
+    Text(&'static str, fn(&T) -> &str),
+    Timestamp(&'static str, fn(&T) -> OffsetDateTime),
+}
+
+impl<T> SortKey<T> {
+    fn column(&self) -> &'static str {
+        match self {
+            Self::Text(column, _) | Self::Timestamp(column, _) => column,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SortValue {
+    Text(String),
+    Timestamp(OffsetDateTime),
+}
+
+/// Pages through rows ordered by a sort key, with ids breaking ties. A page
+/// picks up from the sort value and id in its cursor rather than an offset,
+/// so later pages cost no more than the first. `last` and `before` walk the
+/// order back from its end, so those pages list rows in reverse
+pub(super) struct Keyset<T> {
+    key: SortKey<T>,
+    direction: SortDirection,
+    forwards: bool,
+    limit: i64,
+    cursor: Option<(SortValue, String)>,
+}
+
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+impl Keyset<entity::Category> {
+    /// Pages through categories in the order a request asks for
+    pub(super) fn categories(
+        pagination: &Cursor,
+        sort: Option<CategorySort>,
+        max: i32,
+    ) -> Result<Self, Status> {
+        let sort = sort.unwrap_or_default();
+        let key: SortKey<entity::Category> = match CategorySortKey::try_from(sort.key)
+            .map_err(|_| Status::invalid_argument("sort key is invalid"))?
+        {
+            CategorySortKey::CreatedAt => SortKey::Timestamp("created_at", |c| c.created_at),
+            CategorySortKey::UpdatedAt => SortKey::Timestamp("updated_at", |c| c.updated_at),
+            CategorySortKey::Name => SortKey::Text("name", |c| &c.name),
+        };
+        let direction = SortDirection::try_from(sort.direction)
+            .map_err(|_| Status::invalid_argument("sort direction is invalid"))?;
+
+        Self::new(pagination, key, direction, max)
+    }
+}
+
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+impl<T: KeysetRow> Keyset<T> {
+    pub(super) fn new(
+        pagination: &Cursor,
+        key: SortKey<T>,
+        direction: SortDirection,
+        max: i32,
+    ) -> Result<Self, Status> {
+        let index = pagination
+            .index
+            .ok_or_else(|| Status::invalid_argument("missing pagination index"))?;
+        if matches!(index, Index::First(n) | Index::Last(n) if n < 0) {
+            return Err(Status::invalid_argument(
+                "first and last cannot be negative",
+            ));
+        }
+
+        let mut keyset = Self {
+            key,
+            direction,
+            forwards: CursorBuilder::is_paginating_from_left(pagination),
+            limit: pagination::query_count(max, &index).into(),
+            cursor: None,
+        };
+
+        if let Some(cursor_type) = pagination
+            .cursor_value
+            .as_ref()
+            .and_then(|value| value.cursor_type.as_ref())
+        {
+            keyset.cursor = Some(keyset.decode(cursor_type)?);
+        }
+
+        Ok(keyset)
+    }
+
+    /// Fetches a page of the rows that meet `filter`, which appends conditions
+    /// starting with `and`, turning each into an edge along with its cursor
+    pub(super) async fn fetch<E>(
+        &self,
+        pool: &PgPool,
+        filter: impl Fn(&mut QueryBuilder<'_, Postgres>),
+        to_edge: impl Fn(T, String) -> Result<E, Status>,
+    ) -> Result<(Vec<E>, PageInfo), Status> {
+        let column = self.key.column();
+        let ascending = (self.direction == SortDirection::Asc) == self.forwards;
+        let (order, beyond, behind) = if ascending {
+            ("asc", ">", "<=")
+        } else {
+            ("desc", "<", ">=")
+        };
+
+        let mut query = QueryBuilder::new(format!("select * from {}", T::SOURCE));
+        filter(&mut query);
+        self.push_cursor(&mut query, beyond);
+        query
+            .push(format_args!(
+                " order by {column} {order}, id {order} limit "
+            ))
+            .push_bind(self.limit + 1);
+
+        let fut_rows = query
+            .build_query_as::<T>()
+            .fetch_all(pool)
+            .instrument(debug_span!("pg.select.*"));
+
+        // the page's extra row tells whether there is more ahead, so only
+        // whether anything lies behind the cursor needs asking
+        let fut_behind = async {
+            if self.cursor.is_none() {
+                return Ok(false);
+            }
+
+            let mut query =
+                QueryBuilder::new(format!("select exists (select 1 from {}", T::SOURCE));
+            filter(&mut query);
+            self.push_cursor(&mut query, behind);
+            query.push(")");
+
+            query
+                .build_query_scalar::<bool>()
+                .fetch_one(pool)
+                .instrument(debug_span!("pg.select.exists"))
+                .await
+        };
+
+        let (rows, behind) = tokio::try_join!(fut_rows, fut_behind).map_err(map_err)?;
+
+        self.page(rows, behind, to_edge)
+    }
+
+    /// Names the sort order in cursors, so that a cursor is only used with
+    /// the order it came from
+    fn sort_name(&self) -> String {
+        let direction = match self.direction {
+            SortDirection::Asc => "asc",
+            SortDirection::Desc => "desc",
+        };
+        format!("{}.{direction}", self.key.column())
+    }
+
+    fn push_cursor(&self, query: &mut QueryBuilder<'_, Postgres>, comparison: &str) {
+        let Some((value, id)) = self.cursor.clone() else {
+            return;
+        };
+
+        query.push(format_args!(
+            " and ({}, id) {comparison} (",
+            self.key.column()
+        ));
+        match value {
+            SortValue::Text(value) => query.push_bind(value),
+            SortValue::Timestamp(value) => query.push_bind(value),
+        };
+        query.push(", ").push_bind(id).push(")");
+    }
+
+    fn page<E>(
+        &self,
+        rows: Vec<T>,
+        behind: bool,
+        to_edge: impl Fn(T, String) -> Result<E, Status>,
+    ) -> Result<(Vec<E>, PageInfo), Status> {
+        let has_more = rows.len() as i64 > self.limit;
+
+        let mut cursors = Vec::with_capacity(rows.len());
+        let edges = rows
+            .into_iter()
+            .take(self.limit as usize)
+            .map(|row| {
+                let cursor = self.encode(&row)?;
+                cursors.push(cursor.clone());
+                to_edge(row, cursor)
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+
+        let (has_next_page, has_previous_page) = if self.forwards {
+            (has_more, behind)
+        } else {
+            (behind, has_more)
+        };
+
+        let page_info = PageInfo {
+            has_next_page,
+            has_previous_page,
+            start_cursor: cursors.first().cloned(),
+            end_cursor: cursors.last().cloned(),
+        };
+
+        Ok((edges, page_info))
+    }
+
+    fn encode(&self, row: &T) -> Result<String, Status> {
+        let value = match &self.key {
+            SortKey::Text(_, value) => value(row).to_string(),
+            SortKey::Timestamp(_, value) => format_timestamp(value(row))?,
+        };
+
+        Ok(CursorBuilder::new(row.id(), &format!("{}:{value}", self.sort_name())).encode())
+    }
+
+    fn decode(&self, cursor_type: &CursorType) -> Result<(SortValue, String), Status> {
+        let cursor = decode_cursor(cursor_type)?;
+
+        let value = cursor
+            .dt()
+            .split_once(':')
+            .filter(|(sort, _)| *sort == self.sort_name())
+            .map(|(_, value)| value)
+            .ok_or_else(|| Status::invalid_argument("cursor is from a different sort order"))?;
+
+        let value = match self.key {
+            SortKey::Text(..) => SortValue::Text(value.to_string()),
+            SortKey::Timestamp(..) => SortValue::Timestamp(
+                OffsetDateTime::parse(value, &Rfc3339)
+                    .map_err(|_| Status::invalid_argument("cursor is invalid"))?,
+            ),
+        };
+
+        Ok((value, cursor.id().to_string()))
+    }
+}
+
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+fn format_timestamp(value: OffsetDateTime) -> Result<String, Status> {
+    value
+        .to_offset(UtcOffset::UTC)
+        .format(&Rfc3339)
+        .map_err(map_err)
+}
@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, bail};
+use sellershut_core::categories::{
+    BulkUpsertCategoriesRequest, Category, bulk_category_result::Outcome,
+    mutate_categories_server::MutateCategories,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{Instrument, debug_span, info};
+
+use crate::{entity, state::AppState, utils};
+
+/// Recorded as the actor in the history of imported categories
+const IMPORT_ACTOR: &str = "import";
+
+const CSV_HEADER: [&str; 6] = ["id", "ap_id", "name", "parent", "image_url", "local"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// The taxonomy as a tree, each category holding its sub-categories
+    Json,
+    /// A row per category, naming its parent by `ap_id`
+    Csv,
+}
+
+/// A category in the JSON export, with its sub-categories nested in order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryNode {
+    pub ap_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default = "default_local")]
+    pub local: bool,
+    #[serde(default)]
+    pub sub_categories: Vec<CategoryNode>,
+}
+
+fn default_local() -> bool {
+    true
+}
+
+/// Dumps every category that has not been deleted
+pub async fn export(pool: &PgPool, format: Format) -> anyhow::Result<String> {
+    let categories = sqlx::query_as!(
+        entity::Category,
+        "select * from category where deleted_at is null order by name, ap_id"
+    )
+    .fetch_all(pool)
+    .instrument(debug_span!("pg.select.*"))
+    .await?;
+
+    let ids: HashMap<_, _> = categories
+        .iter()
+        .map(|c| (c.ap_id.clone(), c.id.clone()))
+        .collect();
+    let tree = build_tree(categories);
+
+    let output = match format {
+        Format::Json => serde_json::to_string_pretty(&tree)?,
+        Format::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            writer.write_record(CSV_HEADER)?;
+            write_csv_nodes(&mut writer, &tree, None, &ids)?;
+            String::from_utf8(writer.into_inner().map_err(|e| e.into_error())?)?
+        }
+    };
+
+    Ok(output)
+}
+
+/// Upserts every category in `input` in a single all or nothing batch, so
+/// importing the same document again leaves the taxonomy as it was. Categories
+/// are matched by `ap_id`; the `id` column of a CSV is ignored, as ids belong to
+/// the database they were exported from. Only JSON categories listing sub-categories have theirs replaced, the
+/// rest keep the stored sub-categories the document does not place elsewhere.
+/// Returns how many categories were imported
+pub async fn import(state: &AppState, format: Format, input: &str) -> anyhow::Result<usize> {
+    let mut listing = HashSet::new();
+    let categories = match format {
+        Format::Json => {
+            let tree: Vec<CategoryNode> = serde_json::from_str(input).context("parsing json")?;
+            with_sub_categories(&tree, &mut listing);
+            let mut categories = Vec::new();
+            flatten(tree, None, &mut categories);
+            categories
+        }
+        Format::Csv => parse_csv(input)?,
+    };
+
+    let mut categories = parents_first(categories);
+    keep_sub_categories(&state.services.postgres, &mut categories, &listing).await?;
+    let ap_ids: Vec<_> = categories.iter().map(|c| c.ap_id.clone()).collect();
+
+    let request = utils::request_as(
+        BulkUpsertCategoriesRequest {
+            categories,
+            all_or_nothing: true,
+        },
+        IMPORT_ACTOR,
+    );
+
+    let results = state.bulk_upsert(request).await?.into_inner().results;
+
+    let rejected: Vec<_> = ap_ids
+        .iter()
+        .zip(results.iter())
+        .filter_map(|(ap_id, result)| match result.outcome.as_ref() {
+            // the rest were only aborted because of these
+            Some(Outcome::Error(error)) if error.code != tonic::Code::Aborted as i32 => {
+                Some(format!("{ap_id}: {}", error.message))
+            }
+            _ => None,
+        })
+        .collect();
+
+    if !rejected.is_empty() {
+        bail!("nothing imported, rejected:\n{}", rejected.join("\n"));
+    }
+
+    let inserted = results.iter().filter(|r| r.inserted).count();
+    info!(imported = results.len(), inserted, "imported categories");
+
+    Ok(results.len())
+}
+
+/// Nests categories beneath their parents, keeping the order of each parent's
+/// `sub_categories`. Categories whose parent is gone become roots. Otherwise
+/// categories keep the order they are given in
+fn build_tree(categories: Vec<entity::Category>) -> Vec<CategoryNode> {
+    let ap_ids: HashSet<_> = categories.iter().map(|c| c.ap_id.clone()).collect();
+    let mut children: HashMap<String, Vec<entity::Category>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for category in categories {
+        match category.parent_id.clone().filter(|p| ap_ids.contains(p)) {
+            Some(parent_id) => children.entry(parent_id).or_default().push(category),
+            None => roots.push(category),
+        }
+    }
+
+    fn node(
+        category: entity::Category,
+        children: &mut HashMap<String, Vec<entity::Category>>,
+    ) -> CategoryNode {
+        let mut subs = children.remove(&category.ap_id).unwrap_or_default();
+        // children missing from sub_categories go last
+        subs.sort_by_key(|sub| {
+            category
+                .sub_categories
+                .iter()
+                .position(|s| *s == sub.ap_id)
+                .unwrap_or(usize::MAX)
+        });
+
+        CategoryNode {
+            sub_categories: subs.into_iter().map(|sub| node(sub, children)).collect(),
+            ap_id: category.ap_id,
+            name: category.name,
+            image_url: category.image_url,
+            local: category.local,
+        }
+    }
+
+    roots
+        .into_iter()
+        .map(|root| node(root, &mut children))
+        .collect()
+}
+
+/// Collects the categories in a tree that list sub-categories
+fn with_sub_categories(nodes: &[CategoryNode], listing: &mut HashSet<String>) {
+    for node in nodes.iter().filter(|node| !node.sub_categories.is_empty()) {
+        listing.insert(node.ap_id.clone());
+        with_sub_categories(&node.sub_categories, listing);
+    }
+}
+
+/// Gives the categories not in `listing` the stored sub-categories that are
+/// either missing from the import or imported under the same parent, as an
+/// upsert would otherwise detach them
+async fn keep_sub_categories(
+    pool: &PgPool,
+    categories: &mut [Category],
+    listing: &HashSet<String>,
+) -> anyhow::Result<()> {
+    let parents: HashMap<_, _> = categories
+        .iter()
+        .map(|c| (c.ap_id.clone(), c.parent_id.clone()))
+        .collect();
+    let ap_ids: Vec<_> = categories
+        .iter()
+        .filter(|c| !listing.contains(&c.ap_id))
+        .map(|c| c.ap_id.clone())
+        .collect();
+
+    let mut stored: HashMap<_, _> = sqlx::query!(
+        "
+        select ap_id, sub_categories from category
+        where ap_id = any($1) and deleted_at is null",
+        &ap_ids
+    )
+    .fetch_all(pool)
+    .instrument(debug_span!("pg.select.sub_categories"))
+    .await?
+    .into_iter()
+    .map(|row| (row.ap_id, row.sub_categories))
+    .collect();
+
+    for category in categories.iter_mut() {
+        let Some(subs) = stored.remove(&category.ap_id) else {
+            continue;
+        };
+
+        category.sub_categories = subs
+            .into_iter()
+            .filter(|sub| {
+                parents
+                    .get(sub)
+                    .is_none_or(|parent| parent.as_ref() == Some(&category.ap_id))
+            })
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Lists the categories in a tree, each after its parent
+pub(crate) fn flatten(
+    nodes: Vec<CategoryNode>,
+    parent_id: Option<&str>,
+    categories: &mut Vec<Category>,
+) {
+    for node in nodes {
+        categories.push(Category {
+            name: node.name,
+            image_url: node.image_url,
+            parent_id: parent_id.map(Into::into),
+            ap_id: node.ap_id.clone(),
+            local: node.local,
+            ..Default::default()
+        });
+        flatten(node.sub_categories, Some(&node.ap_id), categories);
+    }
+}
+
+/// Orders categories so each comes after its parent, as the bulk upsert
+/// requires when both are in the batch
+pub(crate) fn parents_first(categories: Vec<Category>) -> Vec<Category> {
+    let index: HashMap<_, _> = categories
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.ap_id.clone(), i))
+        .collect();
+
+    let mut visited = vec![false; categories.len()];
+    let mut order = Vec::with_capacity(categories.len());
+
+    for start in 0..categories.len() {
+        let mut chain = Vec::new();
+        let mut current = Some(start);
+        while let Some(i) = current.filter(|i| !visited[*i]) {
+            visited[i] = true;
+            chain.push(i);
+            current = categories[i]
+                .parent_id
+                .as_ref()
+                .and_then(|p| index.get(p).copied());
+        }
+        order.extend(chain.into_iter().rev());
+    }
+
+    let mut categories: Vec<_> = categories.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|i| categories[i].take())
+        .collect()
+}
+
+/// A category in the CSV export
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    id: String,
+    ap_id: String,
+    name: String,
+    parent: Option<String>,
+    image_url: Option<String>,
+    local: bool,
+}
+
+fn write_csv_nodes(
+    writer: &mut csv::Writer<Vec<u8>>,
+    nodes: &[CategoryNode],
+    parent_id: Option<&str>,
+    ids: &HashMap<String, String>,
+) -> csv::Result<()> {
+    for node in nodes {
+        writer.serialize(CsvRow {
+            id: ids[&node.ap_id].clone(),
+            ap_id: node.ap_id.clone(),
+            name: node.name.clone(),
+            parent: parent_id.map(Into::into),
+            image_url: node.image_url.clone(),
+            local: node.local,
+        })?;
+        write_csv_nodes(writer, &node.sub_categories, Some(&node.ap_id), ids)?;
+    }
+    Ok(())
+}
+
+fn parse_csv(input: &str) -> anyhow::Result<Vec<Category>> {
+    let mut reader = csv::Reader::from_reader(input.as_bytes());
+
+    let header = reader.headers().context("parsing csv")?;
+    if *header != CSV_HEADER[..] {
+        bail!(
+            "expected a csv header of {}, got {}",
+            CSV_HEADER.join(","),
+            header.iter().collect::<Vec<_>>().join(",")
+        );
+    }
+
+    reader
+        .deserialize()
+        .map(|row| {
+            let row: CsvRow = row.context("parsing csv")?;
+            // the upsert keeps the id of a stored category and generates one
+            // for a new category, so `row.id` is left out
+            Ok(Category {
+                ap_id: row.ap_id,
+                name: row.name,
+                parent_id: row.parent,
+                image_url: row.image_url,
+                local: row.local,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
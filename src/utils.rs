@@ -1,7 +1,13 @@
-use sellershut_core::categories::Category;
-use tracing::error;
+use sellershut_core::{categories::Category, google::protobuf::FieldMask};
+use sqlx::PgConnection;
+use tonic::{
+    Request,
+    metadata::{MetadataMap, MetadataValue},
+};
+use tracing::{Instrument, debug_span, error};
 use url::Url;
 
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
 pub fn validate_input(category: &Category) -> Result<(), tonic::Status> {
     if let Some(ref url) = category.image_url {
         check_url(url)?;
@@ -17,6 +23,59 @@ pub fn validate_input(category: &Category) -> Result<(), tonic::Status> {
     Ok(())
 }
 
+/// Copies the fields named in `mask` from `update` onto `category`
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
+pub fn apply_field_mask(
+    category: &mut Category,
+    update: &Category,
+    mask: &FieldMask,
+) -> Result<(), tonic::Status> {
+    if mask.paths.is_empty() {
+        return Err(tonic::Status::invalid_argument(
+            "update_mask must list at least one field",
+        ));
+    }
+
+    for path in mask.paths.iter() {
+        match path.as_str() {
+            "name" => category.name = update.name.clone(),
+            "image_url" => category.image_url = update.image_url.clone(),
+            "parent_id" => category.parent_id = update.parent_id.clone(),
+            "sub_categories" => category.sub_categories = update.sub_categories.clone(),
+            path => {
+                let msg = format!("unknown field in update_mask: {path}");
+                error!(msg);
+                return Err(tonic::Status::invalid_argument(msg));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Request metadata naming who made a change, recorded in the category history
+pub const ACTOR_METADATA_KEY: &str = "x-actor";
+
+/// Reads the actor making a request from its metadata, ignoring blank or non-ascii values
+pub fn actor(metadata: &MetadataMap) -> Option<String> {
+    metadata
+        .get(ACTOR_METADATA_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+/// Wraps a message for calling an rpc in process, on behalf of `actor`
+pub fn request_as<T>(message: T, actor: &'static str) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .metadata_mut()
+        .insert(ACTOR_METADATA_KEY, MetadataValue::from_static(actor));
+    request
+}
+
+#[allow(clippy::result_large_err, reason = "rpcs return tonic::Status")]
 pub fn check_url(value: &str) -> Result<Url, tonic::Status> {
     Url::parse(value).map_err(|_e| {
         let msg = format!("invalid valid url: {value}");
@@ -24,3 +83,129 @@ pub fn check_url(value: &str) -> Result<Url, tonic::Status> {
         tonic::Status::failed_precondition(msg)
     })
 }
+
+/// Ensures the parent and sub-categories referenced by a category exist
+pub async fn check_references(
+    conn: &mut PgConnection,
+    category: &Category,
+) -> Result<(), tonic::Status> {
+    let mut references = category.sub_categories.clone();
+    if let Some(ref parent_id) = category.parent_id {
+        references.push(parent_id.to_owned());
+    }
+
+    if references.is_empty() {
+        return Ok(());
+    }
+
+    let existing = sqlx::query_scalar!(
+        "select ap_id from category where ap_id = any($1) and deleted_at is null",
+        &references
+    )
+    .fetch_all(conn)
+    .instrument(debug_span!("pg.select.ap_id"))
+    .await
+    .map_err(|e| tonic::Status::internal(e.to_string()))?;
+
+    if let Some(parent_id) = category
+        .parent_id
+        .as_ref()
+        .filter(|parent_id| !existing.contains(parent_id))
+    {
+        let msg = format!("parent category does not exist: {parent_id}");
+        error!(msg);
+        return Err(tonic::Status::failed_precondition(msg));
+    }
+
+    if let Some(missing) = category
+        .sub_categories
+        .iter()
+        .find(|sub_category| !existing.contains(sub_category))
+    {
+        let msg = format!("sub-category does not exist: {missing}");
+        error!(msg);
+        return Err(tonic::Status::failed_precondition(msg));
+    }
+
+    Ok(())
+}
+
+/// Advisory lock key held by every transaction that changes the hierarchy
+pub const HIERARCHY_LOCK: i64 = 0x6361_7465_676f_7279;
+
+/// Holds the hierarchy lock until the transaction ends. The ancestors a cycle
+/// check reads are not locked, so without it two moves, each fine alone, could
+/// both pass their checks and commit a cycle between them. Take it before
+/// locking any category rows, or two writers can end up waiting on each other
+pub async fn lock_hierarchy(conn: &mut PgConnection) -> Result<(), tonic::Status> {
+    sqlx::query("select pg_advisory_xact_lock($1)")
+        .bind(HIERARCHY_LOCK)
+        .execute(conn)
+        .instrument(debug_span!("pg.select.advisory_lock"))
+        .await
+        .map_err(|e| tonic::Status::internal(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Ensures a category would not become its own ancestor, either through its
+/// parent or by taking one of its ancestors as a sub-category. The caller holds
+/// the hierarchy lock, so the check still holds when the transaction commits
+pub async fn check_cycles(
+    conn: &mut PgConnection,
+    category: &Category,
+) -> Result<(), tonic::Status> {
+    let cycle_err = |path: &[&str]| {
+        let msg = format!(
+            "category would become its own ancestor: {}",
+            path.join(" -> ")
+        );
+        error!(msg);
+        tonic::Status::invalid_argument(msg)
+    };
+
+    if category.sub_categories.contains(&category.ap_id) {
+        return Err(cycle_err(&[&category.ap_id, &category.ap_id]));
+    }
+
+    let Some(ref parent_id) = category.parent_id else {
+        return Ok(());
+    };
+
+    let ancestors = sqlx::query_scalar!(
+        "
+            with recursive ancestors (ap_id, parent_id, path) as (
+                select ap_id, parent_id, array[ap_id]
+                from category
+                where ap_id = $1
+                union all
+                select c.ap_id, c.parent_id, a.path || c.ap_id
+                from category c
+                join ancestors a on c.ap_id = a.parent_id
+                where a.ap_id <> $2 and not c.ap_id = any(a.path)
+            )
+            select ap_id as \"ap_id!\" from ancestors order by cardinality(path)
+        ",
+        parent_id,
+        &category.ap_id
+    )
+    .fetch_all(conn)
+    .instrument(debug_span!("pg.select.ancestors"))
+    .await
+    .map_err(|e| tonic::Status::internal(e.to_string()))?;
+
+    let mut path = vec![category.ap_id.as_str()];
+    for ancestor in ancestors.iter() {
+        path.push(ancestor);
+        if ancestor == &category.ap_id {
+            return Err(cycle_err(&path));
+        }
+        // the sub-category would move beneath this category
+        if category.sub_categories.contains(ancestor) {
+            path.push(&category.ap_id);
+            return Err(cycle_err(&path));
+        }
+    }
+
+    Ok(())
+}
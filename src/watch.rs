@@ -0,0 +1,212 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use sellershut_core::categories::{CategoryChange, ChangeKind};
+use serde::Deserialize;
+use sqlx::{PgPool, postgres::PgListener};
+use tokio::sync::broadcast;
+use tracing::{Instrument, debug, debug_span, error, trace, warn};
+
+use crate::entity;
+
+/// Postgres channel the `category` table triggers notify on
+pub const CHANNEL: &str = "category_changes";
+
+/// Notifications a slow watcher can fall behind by before it is disconnected
+pub const CAPACITY: usize = 1024;
+
+/// How long the listener first waits before trying to reach postgres again,
+/// doubling up to `MAX_RETRY_DELAY` while it stays unreachable
+const MIN_RETRY_DELAY: Duration = Duration::from_millis(100);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl From<NotificationKind> for ChangeKind {
+    fn from(value: NotificationKind) -> Self {
+        match value {
+            NotificationKind::Created => ChangeKind::Created,
+            NotificationKind::Updated => ChangeKind::Updated,
+            NotificationKind::Deleted => ChangeKind::Deleted,
+        }
+    }
+}
+
+/// A change announced by the `notify_category_change` trigger
+#[derive(Debug, Clone, Deserialize)]
+pub struct CategoryNotification {
+    pub kind: NotificationKind,
+    pub ap_id: String,
+    pub parent_id: Option<String>,
+    /// The parent before an update, so a category moving out of a subtree is
+    /// still seen by its watchers
+    pub previous_parent_id: Option<String>,
+}
+
+/// What the watchers subscribed to `AppState::changes` receive
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    Changed(Arc<ChangedCategory>),
+    /// The listener lost its connection, or could not load a change, so
+    /// watchers may have missed changes
+    Interrupted,
+}
+
+/// A committed change, loaded once for every watcher
+#[derive(Debug)]
+pub struct ChangedCategory {
+    pub notification: CategoryNotification,
+    /// The category as it is now, unless it has been purged
+    pub category: Option<entity::Category>,
+    /// The categories above it, both before and after the change. Deleted
+    /// categories still count, so their deletion reaches the watcher
+    pub ancestors: HashSet<String>,
+}
+
+impl ChangedCategory {
+    /// Whether the change is within the subtree of `root`, or anywhere if
+    /// there is no root
+    pub fn in_subtree(&self, root: Option<&str>) -> bool {
+        root.is_none_or(|root| self.notification.ap_id == root || self.ancestors.contains(root))
+    }
+
+    pub fn to_change(&self) -> CategoryChange {
+        CategoryChange {
+            kind: ChangeKind::from(self.notification.kind).into(),
+            ap_id: self.notification.ap_id.clone(),
+            category: self.category.clone().map(Into::into),
+        }
+    }
+}
+
+/// Starts listening for category changes. Changes made before this returns are
+/// not seen
+pub async fn listen(pool: &PgPool) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    debug!(channel = CHANNEL, "listening for category changes");
+    Ok(listener)
+}
+
+/// Hands every category change to the watchers subscribed to `sender`. Changes
+/// notified while the connection is down are lost, so watchers are told
+/// whenever that may have happened
+pub async fn forward(
+    mut listener: PgListener,
+    pool: PgPool,
+    sender: broadcast::Sender<WatchEvent>,
+) {
+    let mut retry_delay = None;
+
+    loop {
+        let notification = match listener.try_recv().await {
+            Ok(Some(notification)) => {
+                retry_delay = None;
+                notification
+            }
+            // the connection was lost and has been re-established
+            Ok(None) => {
+                warn!("category change listener reconnected");
+                let _ = sender.send(WatchEvent::Interrupted);
+                continue;
+            }
+            // the server is shutting down
+            Err(sqlx::Error::PoolClosed) => break,
+            Err(e) => {
+                if retry_delay.is_none() {
+                    let _ = sender.send(WatchEvent::Interrupted);
+                }
+                let delay = retry_delay.map_or(MIN_RETRY_DELAY, |delay: Duration| {
+                    (delay * 2).min(MAX_RETRY_DELAY)
+                });
+                retry_delay = Some(delay);
+
+                error!(retry_in = ?delay, "category change listener: {e}");
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+        };
+
+        let notification =
+            match serde_json::from_str::<CategoryNotification>(notification.payload()) {
+                Ok(notification) => notification,
+                Err(e) => {
+                    error!(payload = notification.payload(), "category change: {e}");
+                    continue;
+                }
+            };
+        trace!(id = notification.ap_id, kind = ?notification.kind, "category changed");
+
+        // nobody watching is fine
+        if sender.receiver_count() == 0 {
+            continue;
+        }
+
+        let event = match load(&pool, notification).await {
+            Ok(changed) => WatchEvent::Changed(Arc::new(changed)),
+            Err(e) => {
+                error!("loading category change: {e}");
+                WatchEvent::Interrupted
+            }
+        };
+        let _ = sender.send(event);
+    }
+}
+
+/// Reads the changed category and its ancestors
+async fn load(
+    pool: &PgPool,
+    notification: CategoryNotification,
+) -> Result<ChangedCategory, sqlx::Error> {
+    let category = sqlx::query_as!(
+        entity::Category,
+        "select * from category where ap_id = $1",
+        &notification.ap_id
+    )
+    .fetch_optional(pool)
+    .instrument(debug_span!("pg.select.*"))
+    .await?;
+
+    let parents: Vec<_> = notification
+        .parent_id
+        .iter()
+        .chain(notification.previous_parent_id.iter())
+        .cloned()
+        .collect();
+
+    let ancestors = if parents.is_empty() {
+        HashSet::new()
+    } else {
+        sqlx::query_scalar!(
+            "
+            with recursive ancestors (ap_id, parent_id, path) as (
+                select ap_id, parent_id, array[ap_id]
+                from category
+                where ap_id = any($1)
+                union all
+                select c.ap_id, c.parent_id, a.path || c.ap_id
+                from category c
+                join ancestors a on c.ap_id = a.parent_id
+                where not c.ap_id = any(a.path)
+            )
+            select distinct ap_id as \"ap_id!\" from ancestors",
+            &parents
+        )
+        .fetch_all(pool)
+        .instrument(debug_span!("pg.select.ancestors"))
+        .await?
+        .into_iter()
+        .collect()
+    };
+
+    Ok(ChangedCategory {
+        notification,
+        category,
+        ancestors,
+    })
+}
@@ -8,22 +8,22 @@ values
 -- Insert sub-categories under Electronics
 insert into category (id, ap_id, local, name, sub_categories, parent_id)
 values
-('4', 'http://localhost/category/subcat1', true, 'Mobile Phones', '{"http://localhost/category/item1", "http://localhost/category/item2"}', 'http://localhost/category/cat1'),
-('5', 'http://localhost/category/subcat2', true, 'Laptops', '{"http://localhost/category/item3", "http://localhost/category/item4"}', 'http://localhost/category/cat1'),
-('6', 'http://localhost/category/subcat3', true, 'Televisions', '{"http://localhost/category/item5", "http://localhost/category/item6"}', 'http://localhost/category/cat1');
+('4', 'http://localhost/category/subcat1', true, 'Mobile Phones', '{"http://localhost/category/item1"}', 'http://localhost/category/cat1'),
+('5', 'http://localhost/category/subcat2', true, 'Laptops', '{"http://localhost/category/item2"}', 'http://localhost/category/cat1'),
+('6', 'http://localhost/category/subcat3', true, 'Televisions', '{"http://localhost/category/item3"}', 'http://localhost/category/cat1');
 
 -- Insert sub-categories under Clothing
 insert into category (id, ap_id, local, name, sub_categories, parent_id)
 values
-('7', 'http://localhost/category/subcat4', true, 'T-Shirts', '{"http://localhost/category/item7", "http://localhost/category/item8"}', 'http://localhost/category/cat2'),
-('8', 'http://localhost/category/subcat5', true, 'Jeans', '{"http://localhost/category/item9", "http://localhost/category/item10"}', 'http://localhost/category/cat2'),
-('9', 'http://localhost/category/subcat6', true, 'Shoes', '{"http://localhost/category/item11", "http://localhost/category/item12"}', 'http://localhost/category/cat2');
+('7', 'http://localhost/category/subcat4', true, 'T-Shirts', '{"http://localhost/category/item4"}', 'http://localhost/category/cat2'),
+('8', 'http://localhost/category/subcat5', true, 'Jeans', '{"http://localhost/category/item5"}', 'http://localhost/category/cat2'),
+('9', 'http://localhost/category/subcat6', true, 'Shoes', '{}', 'http://localhost/category/cat2');
 
 -- Insert categories under Books
 insert into category (id, ap_id, local, name, sub_categories, parent_id)
 values
-('10', 'http://localhost/category/subcat7', false, 'Fiction', '{"http://localhost/category/item13", "http://localhost/category/item14"}', 'http://localhost/category/cat3'),
-('11', 'http://localhost/category/subcat8', false, 'Non-Fiction', '{"http://localhost/category/item15", "http://localhost/category/item16"}', 'http://localhost/category/cat3');
+('10', 'http://localhost/category/subcat7', false, 'Fiction', '{"http://localhost/category/item6"}', 'http://localhost/category/cat3'),
+('11', 'http://localhost/category/subcat8', false, 'Non-Fiction', '{"http://localhost/category/item7"}', 'http://localhost/category/cat3');
 
 -- Insert specific items or deeper sub-categories
 insert into category (id, ap_id, local, name, parent_id)
@@ -0,0 +1,185 @@
+use anyhow::Result;
+use categories_service::{
+    google::{self, GoogleCategory},
+    seed::SeedSummary,
+};
+use sqlx::PgPool;
+use url::Url;
+
+use crate::helpers::TestApp;
+
+const TAXONOMY: &str = include_str!("../fixtures/taxonomy-with-ids.en-US.txt");
+
+fn base_url() -> Url {
+    Url::parse("http://localhost/category/").unwrap()
+}
+
+#[test]
+fn parse_google_taxonomy() -> Result<()> {
+    let categories = google::parse(TAXONOMY)?;
+
+    assert_eq!(categories.len(), 19);
+    assert_eq!(
+        categories[1],
+        GoogleCategory {
+            id: "3237".into(),
+            path: vec!["Animals & Pet Supplies".into(), "Live Animals".into()]
+        }
+    );
+
+    assert!(google::parse("Electronics").is_err());
+    assert!(google::parse("12a - Electronics").is_err());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn import_google_taxonomy(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    let summary = google::import(&app.state, TAXONOMY, &base_url()).await?;
+    assert_eq!(summary.inserted, 19);
+
+    let phones = sqlx::query!(
+        "
+        select c.name, c.parent_id
+        from category_external_id e
+        join category c on c.ap_id = e.category_ap_id
+        where e.taxonomy = 'google' and e.external_id = '267'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(phones.name, "Mobile Phones");
+    assert_eq!(
+        phones.parent_id.as_deref(),
+        Some("http://localhost/category/google-270")
+    );
+
+    let sub_categories = sqlx::query_scalar!(
+        "select sub_categories from category where ap_id = 'http://localhost/category/google-2'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(
+        sub_categories,
+        [
+            "http://localhost/category/google-3",
+            "http://localhost/category/google-4",
+            "http://localhost/category/google-5"
+        ]
+    );
+
+    // unchanged, so nothing is written
+    let summary = google::import(&app.state, TAXONOMY, &base_url()).await?;
+    assert_eq!(summary, SeedSummary::default());
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn import_newer_google_taxonomy(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    google::import(&app.state, TAXONOMY, &base_url()).await?;
+
+    // a category mapped by hand keeps its ap_id
+    sqlx::query!(
+        "
+        insert into category (id, ap_id, name, local)
+        values ('x', 'http://localhost/category/handheld', 'Handheld', true)"
+    )
+    .execute(pool)
+    .await?;
+
+    let newer = TAXONOMY
+        .replace(
+            "328 - Electronics > Computers > Laptops",
+            "328 - Electronics > Computers > Laptop Computers",
+        )
+        .replace(
+            "5322 - Apparel & Accessories > Clothing > Activewear",
+            "5322 - Apparel & Accessories > Activewear",
+        )
+        + "4745 - Electronics > Computers > Handheld Devices\n";
+
+    sqlx::query!(
+        "
+        insert into category_external_id (taxonomy, external_id, category_ap_id)
+        values ('google', '4745', 'http://localhost/category/handheld')"
+    )
+    .execute(pool)
+    .await?;
+
+    let summary = google::import(&app.state, &newer, &base_url()).await?;
+    assert_eq!(
+        summary,
+        SeedSummary {
+            inserted: 0,
+            updated: 3,
+            deleted: 0
+        }
+    );
+
+    let laptops = sqlx::query_scalar!(
+        "select name from category where ap_id = 'http://localhost/category/google-328'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(laptops, "Laptop Computers");
+
+    let activewear = sqlx::query_scalar!(
+        "select parent_id from category where ap_id = 'http://localhost/category/google-5322'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(
+        activewear.as_deref(),
+        Some("http://localhost/category/google-166")
+    );
+
+    let handheld = sqlx::query!(
+        "select name, parent_id from category where ap_id = 'http://localhost/category/handheld'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(handheld.name, "Handheld Devices");
+    assert_eq!(
+        handheld.parent_id.as_deref(),
+        Some("http://localhost/category/google-278")
+    );
+
+    Ok(())
+}
+
+#[sqlx::test]
+async fn import_remapped_google_id(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    google::import(&app.state, TAXONOMY, &base_url()).await?;
+
+    // mapped to an id the taxonomy does not list, so laptops are new to it again
+    sqlx::query!(
+        "
+        update category_external_id set external_id = '9999'
+        where taxonomy = 'google' and external_id = '328'"
+    )
+    .execute(pool)
+    .await?;
+
+    let summary = google::import(&app.state, TAXONOMY, &base_url()).await?;
+    assert_eq!(summary, SeedSummary::default());
+
+    let external_ids = sqlx::query_scalar!(
+        "
+        select external_id from category_external_id
+        where taxonomy = 'google' and category_ap_id = 'http://localhost/category/google-328'"
+    )
+    .fetch_all(pool)
+    .await?;
+    assert_eq!(external_ids, ["328"]);
+
+    Ok(())
+}
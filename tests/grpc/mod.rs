@@ -1,2 +1,6 @@
+mod google;
 mod mutate;
+mod outbox;
 mod query;
+mod seed;
+mod transfer;
@@ -1,3 +1,9 @@
+mod bulk;
 mod create;
 mod delete;
+mod external_ids;
+mod purge;
+mod restore;
+mod selections;
+mod update;
 mod upsert;
@@ -0,0 +1,285 @@
+use anyhow::Result;
+use sellershut_core::categories::{
+    BulkCategoryResult, BulkUpsertCategoriesRequest, Category, DeleteCategoryRequest,
+    GetCategoryByIdRequest, GetCategoryRequest, bulk_category_result::Outcome,
+    delete_category_request::Identifier,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+fn category(ap_id: &str, name: &str, parent_id: Option<&str>) -> Category {
+    Category {
+        name: name.into(),
+        ap_id: ap_id.into(),
+        parent_id: parent_id.map(Into::into),
+        local: true,
+        ..Default::default()
+    }
+}
+
+fn stored(result: &BulkCategoryResult) -> &Category {
+    match result.outcome.as_ref() {
+        Some(Outcome::Category(category)) => category,
+        outcome => panic!("expected a category, got {outcome:?}"),
+    }
+}
+
+fn error_code(result: &BulkCategoryResult) -> Code {
+    match result.outcome.as_ref() {
+        Some(Outcome::Error(error)) => Code::from(error.code),
+        outcome => panic!("expected an error, got {outcome:?}"),
+    }
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn bulk_upsert_tree(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let root = "http://localhost/category/garden";
+
+    let request = BulkUpsertCategoriesRequest {
+        categories: vec![
+            category(root, "Garden", None),
+            category("http://localhost/category/tools", "Tools", Some(root)),
+            category("http://localhost/category/plants", "Plants", Some(root)),
+            category("http://localhost/category/item7", "Memoirs", None),
+        ],
+        all_or_nothing: false,
+    };
+
+    let response = app
+        .mutate
+        .bulk_upsert(request.into_request())
+        .await?
+        .into_inner();
+
+    assert_eq!(response.results.len(), 4);
+
+    let inserted: Vec<_> = response.results.iter().map(|r| r.inserted).collect();
+    assert_eq!(inserted, [true, true, true, false]);
+
+    assert_eq!(
+        stored(&response.results[0]).sub_categories,
+        [
+            "http://localhost/category/tools",
+            "http://localhost/category/plants"
+        ]
+    );
+    assert_eq!(stored(&response.results[3]).name, "Memoirs");
+
+    // item7 left subcat8
+    let getter = GetCategoryByIdRequest {
+        id: "11".into(),
+        ..Default::default()
+    }
+    .into_request();
+    let subcat8 = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(subcat8.sub_categories.is_empty());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn bulk_upsert_adopts_sub_categories(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/gadgets";
+    let subcat1 = "http://localhost/category/subcat1";
+
+    let mut gadgets = category(ap_id, "Gadgets", None);
+    gadgets.sub_categories = vec![subcat1.into()];
+
+    let request = BulkUpsertCategoriesRequest {
+        categories: vec![gadgets],
+        all_or_nothing: false,
+    };
+
+    app.mutate.bulk_upsert(request.into_request()).await?;
+
+    let getter = GetCategoryByIdRequest {
+        id: "4".into(),
+        ..Default::default()
+    }
+    .into_request();
+    let moved = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(moved.parent_id.as_deref(), Some(ap_id));
+
+    let getter = GetCategoryByIdRequest {
+        id: "1".into(),
+        ..Default::default()
+    }
+    .into_request();
+    let cat1 = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(!cat1.sub_categories.contains(&subcat1.to_string()));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn bulk_upsert_reports_each_item(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let mut bad_image = category("http://localhost/category/a", "A", None);
+    bad_image.image_url = Some("not a url".into());
+
+    let request = BulkUpsertCategoriesRequest {
+        categories: vec![
+            category("http://localhost/category/ok", "Ok", None),
+            bad_image,
+            category(
+                "http://localhost/category/b",
+                "B",
+                Some("http://localhost/category/missing"),
+            ),
+            category("http://localhost/category/ok", "Again", None),
+            // its parent comes later in the batch
+            category(
+                "http://localhost/category/c",
+                "C",
+                Some("http://localhost/category/d"),
+            ),
+            category("http://localhost/category/d", "D", None),
+            category(
+                "http://localhost/category/cat1",
+                "Electronics",
+                Some("http://localhost/category/item1"),
+            ),
+        ],
+        all_or_nothing: false,
+    };
+
+    let response = app
+        .mutate
+        .bulk_upsert(request.into_request())
+        .await?
+        .into_inner();
+
+    let results = &response.results;
+    assert_eq!(stored(&results[0]).name, "Ok");
+    assert_eq!(error_code(&results[1]), Code::FailedPrecondition);
+    assert_eq!(error_code(&results[2]), Code::FailedPrecondition);
+    assert_eq!(error_code(&results[3]), Code::InvalidArgument);
+    assert_eq!(error_code(&results[4]), Code::FailedPrecondition);
+    assert_eq!(stored(&results[5]).name, "D");
+    assert_eq!(error_code(&results[6]), Code::InvalidArgument);
+
+    let getter = GetCategoryRequest {
+        ap_id: "http://localhost/category/ok".into(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app
+        .query
+        .category_by_ap_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(response.name, "Ok");
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn bulk_upsert_all_or_nothing(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let request = BulkUpsertCategoriesRequest {
+        categories: vec![
+            category("http://localhost/category/ok", "Ok", None),
+            category(
+                "http://localhost/category/b",
+                "B",
+                Some("http://localhost/category/missing"),
+            ),
+        ],
+        all_or_nothing: true,
+    };
+
+    let response = app
+        .mutate
+        .bulk_upsert(request.into_request())
+        .await?
+        .into_inner();
+
+    assert_eq!(error_code(&response.results[0]), Code::Aborted);
+    assert_eq!(error_code(&response.results[1]), Code::FailedPrecondition);
+
+    let getter = GetCategoryRequest {
+        ap_id: "http://localhost/category/ok".into(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app
+        .query
+        .category_by_ap_id(getter)
+        .await?
+        .into_inner()
+        .category;
+
+    assert!(response.is_none());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn bulk_upsert_rejects_deleted(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let delete_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId("http://localhost/category/cat3".into())),
+        expected_updated_at: None,
+    };
+    app.mutate.delete(delete_request.into_request()).await?;
+
+    let request = BulkUpsertCategoriesRequest {
+        categories: vec![
+            category("http://localhost/category/cat3", "Books", None),
+            category("http://localhost/category/ok", "Ok", None),
+        ],
+        all_or_nothing: false,
+    };
+
+    let response = app
+        .mutate
+        .bulk_upsert(request.into_request())
+        .await?
+        .into_inner();
+
+    let results = &response.results;
+    assert_eq!(error_code(&results[0]), Code::FailedPrecondition);
+    assert!(results[1].inserted);
+
+    let deleted = sqlx::query_scalar!(
+        "select deleted_at is not null as \"deleted!\" from category where ap_id = 'http://localhost/category/cat3'"
+    )
+    .fetch_one(&app.state.services.postgres)
+    .await?;
+    assert!(deleted);
+
+    Ok(())
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
-use sellershut_core::categories::{Category, CreateCategoryRequest, GetCategoryRequest};
+use sellershut_core::categories::{
+    Category, CreateCategoryRequest, DeleteCategoryRequest, GetCategoryByIdRequest,
+    GetCategoryRequest, delete_category_request::Identifier,
+};
 use sqlx::PgPool;
-use tonic::IntoRequest;
+use tonic::{Code, IntoRequest};
 
 use crate::helpers::TestApp;
 
@@ -31,6 +34,7 @@ async fn create_category(pool: PgPool) -> Result<()> {
 
     let getter = GetCategoryRequest {
         ap_id: response.ap_id,
+        ..Default::default()
     }
     .into_request();
 
@@ -45,3 +49,222 @@ async fn create_category(pool: PgPool) -> Result<()> {
 
     Ok(())
 }
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn create_category_missing_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Something".into(),
+        ap_id: "http://localhost".into(),
+        parent_id: Some("http://localhost/category/missing".into()),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app.mutate.create(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn create_category_missing_sub_category(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Something".into(),
+        ap_id: "http://localhost".into(),
+        sub_categories: vec![
+            "http://localhost/category/item1".into(),
+            "http://localhost/category/missing".into(),
+        ],
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app.mutate.create(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn create_category_added_to_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Something".into(),
+        ap_id: "http://localhost".into(),
+        parent_id: Some("http://localhost/category/cat1".into()),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app
+        .mutate
+        .create(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    let getter = GetCategoryByIdRequest {
+        id: "1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let parent = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(parent.sub_categories.contains(&response.ap_id));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn create_category_takes_sub_categories(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let sub_category = "http://localhost/category/item1".to_string();
+
+    let category = Category {
+        name: "Something".into(),
+        ap_id: "http://localhost".into(),
+        sub_categories: vec![sub_category.clone()],
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app
+        .mutate
+        .create(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    let getter = GetCategoryByIdRequest {
+        id: "12".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let child = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(child.parent_id, Some(response.ap_id));
+
+    // no longer listed by its previous parent
+    let getter = GetCategoryByIdRequest {
+        id: "4".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let previous_parent = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(!previous_parent.sub_categories.contains(&sub_category));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn create_category_cycle(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    // cat1 would end up beneath its own sub-category
+    let category = Category {
+        name: "Something".into(),
+        ap_id: "http://localhost".into(),
+        parent_id: Some("http://localhost/category/subcat1".into()),
+        sub_categories: vec!["http://localhost/category/cat1".into()],
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app.mutate.create(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn create_category_deleted_ap_id(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.clone())),
+        expected_updated_at: None,
+    };
+
+    app.mutate.delete(category_request.into_request()).await?;
+
+    let category = Category {
+        name: "Something".into(),
+        ap_id,
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let status = app
+        .mutate
+        .create(category_request.into_request())
+        .await
+        .unwrap_err();
+
+    assert_eq!(status.code(), Code::AlreadyExists);
+    assert!(status.message().contains("restore"));
+
+    Ok(())
+}
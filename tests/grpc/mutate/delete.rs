@@ -1,7 +1,13 @@
 use anyhow::Result;
-use sellershut_core::categories::{DeleteCategoryRequest, GetCategoryRequest};
+use sellershut_core::{
+    categories::{
+        DeleteCategoryRequest, GetCategoryByIdRequest, GetCategoryRequest,
+        delete_category_request::Identifier,
+    },
+    google::protobuf::Timestamp,
+};
 use sqlx::PgPool;
-use tonic::IntoRequest;
+use tonic::{Code, IntoRequest};
 
 use crate::helpers::TestApp;
 
@@ -11,7 +17,47 @@ async fn delete_category(pool: PgPool) -> Result<()> {
     let ap_id = "http://localhost/category/item7".to_string();
 
     let category_request = DeleteCategoryRequest {
-        ap_id: ap_id.clone(),
+        identifier: Some(Identifier::ApId(ap_id.clone())),
+        expected_updated_at: None,
+    };
+
+    let deleted = app
+        .mutate
+        .delete(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(deleted.ap_id, ap_id);
+
+    let getter = GetCategoryRequest {
+        ap_id,
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app
+        .query
+        .category_by_ap_id(getter)
+        .await?
+        .into_inner()
+        .category;
+
+    assert!(response.is_none());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn delete_category_cascades(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(
+            "http://localhost/category/cat1".to_string(),
+        )),
+        expected_updated_at: None,
     };
 
     app.mutate
@@ -19,11 +65,100 @@ async fn delete_category(pool: PgPool) -> Result<()> {
         .await?
         .into_inner();
 
-    let getter = GetCategoryRequest { ap_id }.into_request();
+    // cat1, its 3 sub-categories and their 3 items
+    let remaining: i64 =
+        sqlx::query_scalar("select count(*) from category where deleted_at is null")
+            .fetch_one(&app.state.services.postgres)
+            .await?;
+
+    assert_eq!(remaining, 18 - 7);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn delete_category_removed_from_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.clone())),
+        expected_updated_at: None,
+    };
+
+    app.mutate
+        .delete(category_request.into_request())
+        .await?
+        .into_inner();
+
+    let getter = GetCategoryByIdRequest {
+        id: "1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let parent = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(!parent.sub_categories.contains(&ap_id));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn delete_category_stale_version(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(
+            "http://localhost/category/item7".to_string(),
+        )),
+        expected_updated_at: Some(Timestamp {
+            seconds: 0,
+            nanos: 0,
+        }),
+    };
+
+    let response = app.mutate.delete(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::Aborted);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn delete_category_by_id(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::Id("18".to_string())),
+        expected_updated_at: None,
+    };
+
+    let deleted = app
+        .mutate
+        .delete(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(deleted.ap_id, "http://localhost/category/item7");
+
+    let getter = GetCategoryByIdRequest {
+        id: "18".to_string(),
+        ..Default::default()
+    }
+    .into_request();
 
     let response = app
         .query
-        .category_by_ap_id(getter)
+        .category_by_id(getter)
         .await?
         .into_inner()
         .category;
@@ -32,3 +167,51 @@ async fn delete_category(pool: PgPool) -> Result<()> {
 
     Ok(())
 }
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn delete_category_not_found(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(
+            "http://localhost/category/typo".to_string(),
+        )),
+        expected_updated_at: None,
+    };
+
+    let response = app.mutate.delete(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+
+    // deleting twice finds nothing the second time
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::Id("18".to_string())),
+        expected_updated_at: None,
+    };
+
+    app.mutate
+        .delete(category_request.clone().into_request())
+        .await?;
+
+    let response = app.mutate.delete(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn delete_category_no_identifier(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: None,
+        expected_updated_at: None,
+    };
+
+    let response = app.mutate.delete(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use sellershut_core::categories::{
+    ExternalId, ExternalTaxonomy, GetCategoryRequest, SetExternalIdsRequest,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+fn external_id(taxonomy: ExternalTaxonomy, id: &str) -> ExternalId {
+    ExternalId {
+        taxonomy: taxonomy.into(),
+        external_id: id.into(),
+    }
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn set_external_ids(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/item1";
+
+    let request = SetExternalIdsRequest {
+        ap_id: ap_id.into(),
+        external_ids: vec![
+            external_id(ExternalTaxonomy::Google, "267"),
+            external_id(ExternalTaxonomy::Ebay, "9355"),
+        ],
+    };
+    let response = app
+        .mutate
+        .set_external_ids(request.into_request())
+        .await?
+        .into_inner();
+
+    assert_eq!(
+        response.external_ids,
+        [
+            external_id(ExternalTaxonomy::Ebay, "9355"),
+            external_id(ExternalTaxonomy::Google, "267"),
+        ]
+    );
+
+    // taxonomies that are not listed are left alone
+    let request = SetExternalIdsRequest {
+        ap_id: ap_id.into(),
+        external_ids: vec![
+            external_id(ExternalTaxonomy::Ebay, ""),
+            external_id(ExternalTaxonomy::Amazon, "7072561011"),
+        ],
+    };
+    app.mutate.set_external_ids(request.into_request()).await?;
+
+    let getter = GetCategoryRequest {
+        ap_id: ap_id.into(),
+        ..Default::default()
+    }
+    .into_request();
+    let response = app.query.category_by_ap_id(getter).await?.into_inner();
+
+    assert_eq!(
+        response.external_ids,
+        [
+            external_id(ExternalTaxonomy::Amazon, "7072561011"),
+            external_id(ExternalTaxonomy::Google, "267"),
+        ]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn set_external_ids_rejected(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let request = SetExternalIdsRequest {
+        ap_id: "http://localhost/category/item1".into(),
+        external_ids: vec![external_id(ExternalTaxonomy::Google, "267")],
+    };
+    app.mutate.set_external_ids(request.into_request()).await?;
+
+    let cases = [
+        (
+            "http://localhost/category/item2",
+            vec![external_id(ExternalTaxonomy::Google, "267")],
+            Code::AlreadyExists,
+        ),
+        (
+            "http://localhost/category/item2",
+            vec![external_id(ExternalTaxonomy::Unspecified, "1")],
+            Code::InvalidArgument,
+        ),
+        (
+            "http://localhost/category/item2",
+            vec![
+                external_id(ExternalTaxonomy::Ebay, "1"),
+                external_id(ExternalTaxonomy::Ebay, "2"),
+            ],
+            Code::InvalidArgument,
+        ),
+        (
+            "http://localhost/category/nope",
+            vec![external_id(ExternalTaxonomy::Ebay, "1")],
+            Code::NotFound,
+        ),
+    ];
+
+    for (ap_id, external_ids, code) in cases {
+        let request = SetExternalIdsRequest {
+            ap_id: ap_id.into(),
+            external_ids,
+        };
+        let status = app
+            .mutate
+            .set_external_ids(request.into_request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), code);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use sellershut_core::categories::{
+    DeleteCategoryRequest, PurgeCategoriesRequest, delete_category_request::Identifier,
+};
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn purge_categories(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    for ap_id in [
+        "http://localhost/category/item6",
+        "http://localhost/category/item7",
+    ] {
+        let category_request = DeleteCategoryRequest {
+            identifier: Some(Identifier::ApId(ap_id.to_string())),
+            expected_updated_at: None,
+        };
+
+        app.mutate
+            .delete(category_request.into_request())
+            .await?
+            .into_inner();
+    }
+
+    // only item7 has been deleted for longer than the retention period
+    sqlx::query(
+        "update category set deleted_at = deleted_at - interval '31 days' where ap_id = $1",
+    )
+    .bind("http://localhost/category/item7")
+    .execute(&app.state.services.postgres)
+    .await?;
+
+    let response = app
+        .mutate
+        .purge(PurgeCategoriesRequest {}.into_request())
+        .await?
+        .into_inner();
+
+    assert_eq!(response.purged, 1);
+
+    let remaining: i64 = sqlx::query_scalar("select count(*) from category")
+        .fetch_one(&app.state.services.postgres)
+        .await?;
+
+    assert_eq!(remaining, 17);
+
+    Ok(())
+}
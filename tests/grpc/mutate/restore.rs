@@ -0,0 +1,112 @@
+use anyhow::Result;
+use sellershut_core::categories::{
+    DeleteCategoryRequest, GetCategoryByIdRequest, GetDescendantsRequest, RestoreCategoryRequest,
+    delete_category_request::Identifier,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn restore_category(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.clone())),
+        expected_updated_at: None,
+    };
+
+    app.mutate
+        .delete(category_request.into_request())
+        .await?
+        .into_inner();
+
+    let category_request = RestoreCategoryRequest {
+        ap_id: ap_id.clone(),
+    };
+
+    let response = app
+        .mutate
+        .restore(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(response.id, "4");
+
+    // listed by its parent again
+    let getter = GetCategoryByIdRequest {
+        id: "1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let parent = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(parent.sub_categories.contains(&ap_id));
+
+    // the sub-category deleted with it is back too
+    let getter = GetDescendantsRequest {
+        ap_id,
+        max_depth: None,
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.descendants(getter).await?.into_inner();
+
+    assert_eq!(response.descendants.len(), 1);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn restore_category_deleted_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(
+            "http://localhost/category/cat1".to_string(),
+        )),
+        expected_updated_at: None,
+    };
+
+    app.mutate
+        .delete(category_request.into_request())
+        .await?
+        .into_inner();
+
+    let category_request = RestoreCategoryRequest {
+        ap_id: "http://localhost/category/subcat1".to_string(),
+    };
+
+    let response = app.mutate.restore(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn restore_category_not_deleted(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = RestoreCategoryRequest {
+        ap_id: "http://localhost/category/cat1".to_string(),
+    };
+
+    let response = app.mutate.restore(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+
+    Ok(())
+}
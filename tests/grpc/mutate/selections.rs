@@ -0,0 +1,42 @@
+use anyhow::Result;
+use sellershut_core::categories::RecordCategorySelectionsRequest;
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn record_selections(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let request = RecordCategorySelectionsRequest {
+        ap_ids: vec![
+            "http://localhost/category/item1".into(),
+            "http://localhost/category/item1".into(),
+            "http://localhost/category/missing".into(),
+        ],
+    };
+    app.mutate.record_selections(request.into_request()).await?;
+
+    let request = RecordCategorySelectionsRequest {
+        ap_ids: vec!["http://localhost/category/item1".into()],
+    };
+    app.mutate.record_selections(request.into_request()).await?;
+
+    let rank = sqlx::query!(
+        "select depth, popularity from category_rank where category_ap_id = 'http://localhost/category/item1'"
+    )
+    .fetch_one(&app.state.services.postgres)
+    .await?;
+    assert_eq!((rank.depth, rank.popularity), (2, 3));
+
+    // selections do not count as a change to the category
+    let history = sqlx::query_scalar!(
+        "select count(*) from category_history where category_ap_id = 'http://localhost/category/item1'"
+    )
+    .fetch_one(&app.state.services.postgres)
+    .await?;
+    assert_eq!(history, Some(0));
+
+    Ok(())
+}
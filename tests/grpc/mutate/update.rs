@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use categories_service::utils::HIERARCHY_LOCK;
+use sellershut_core::{
+    categories::{Category, GetCategoryByIdRequest, UpdateCategoryRequest},
+    google::protobuf::FieldMask,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn update_category_name(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let name = "Electro-test";
+    let category_request = UpdateCategoryRequest {
+        ap_id: "http://localhost/category/cat1".into(),
+        category: Some(Category {
+            name: name.into(),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    let response = app
+        .mutate
+        .update(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(response.name, name);
+    // fields outside the mask are left alone
+    assert_eq!(
+        response.image_url.as_deref(),
+        Some("https://example.com/electronics.jpg")
+    );
+    assert_eq!(response.sub_categories.len(), 3);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn update_category_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let category_request = UpdateCategoryRequest {
+        ap_id: ap_id.clone(),
+        category: Some(Category {
+            parent_id: Some("http://localhost/category/cat2".into()),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["parent_id".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    let response = app
+        .mutate
+        .update(category_request.into_request())
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert_eq!(response.name, "Mobile Phones");
+    assert_eq!(response.sub_categories.len(), 1);
+
+    let getter = GetCategoryByIdRequest {
+        id: "2".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let parent = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(parent.sub_categories.contains(&ap_id));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn update_category_unknown_field(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = UpdateCategoryRequest {
+        ap_id: "http://localhost/category/cat1".into(),
+        category: Some(Category::default()),
+        update_mask: Some(FieldMask {
+            paths: vec!["id".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    let response = app.mutate.update(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn update_category_missing(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = UpdateCategoryRequest {
+        ap_id: "http://localhost/category/missing".into(),
+        category: Some(Category::default()),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    let response = app.mutate.update(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn update_category_expected_version(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetCategoryByIdRequest {
+        id: "1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let existing = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    let category_request = UpdateCategoryRequest {
+        ap_id: existing.ap_id.clone(),
+        category: Some(Category {
+            name: "Electro-test".into(),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: existing.updated_at,
+    };
+
+    app.mutate
+        .update(category_request.into_request())
+        .await?
+        .into_inner();
+
+    // a second edit based on the same read has been overtaken
+    let category_request = UpdateCategoryRequest {
+        ap_id: existing.ap_id,
+        category: Some(Category {
+            name: "Electro-other".into(),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: existing.updated_at,
+    };
+
+    let response = app.mutate.update(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::Aborted);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn concurrent_moves_cannot_form_cycle(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool.clone()).await;
+
+    let move_under = |ap_id: &str, parent_id: &str| UpdateCategoryRequest {
+        ap_id: ap_id.into(),
+        category: Some(Category {
+            parent_id: Some(parent_id.into()),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["parent_id".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    // hold the hierarchy back so both moves start before either commits
+    let mut tx = pool.begin().await?;
+    sqlx::query("select pg_advisory_xact_lock($1)")
+        .bind(HIERARCHY_LOCK)
+        .execute(&mut *tx)
+        .await?;
+
+    let (mut first, mut second) = (app.mutate.clone(), app.mutate.clone());
+    let first = tokio::spawn(async move {
+        first
+            .update(
+                move_under(
+                    "http://localhost/category/subcat1",
+                    "http://localhost/category/subcat2",
+                )
+                .into_request(),
+            )
+            .await
+    });
+    let second = tokio::spawn(async move {
+        second
+            .update(
+                move_under(
+                    "http://localhost/category/subcat2",
+                    "http://localhost/category/subcat1",
+                )
+                .into_request(),
+            )
+            .await
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tx.commit().await?;
+
+    let results = [first.await?, second.await?];
+    let failed: Vec<_> = results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .collect();
+
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].code(), Code::InvalidArgument);
+
+    Ok(())
+}
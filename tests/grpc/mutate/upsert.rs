@@ -1,7 +1,13 @@
 use anyhow::Result;
-use sellershut_core::categories::{Category, GetCategoryRequest, UpsertCategoryRequest};
+use sellershut_core::{
+    categories::{
+        Category, DeleteCategoryRequest, GetCategoryByIdRequest, GetCategoryRequest,
+        UpsertCategoryRequest, delete_category_request::Identifier,
+    },
+    google::protobuf::Timestamp,
+};
 use sqlx::PgPool;
-use tonic::IntoRequest;
+use tonic::{Code, IntoRequest};
 
 use crate::helpers::TestApp;
 
@@ -25,12 +31,13 @@ async fn upsert_category_create(pool: PgPool) -> Result<()> {
         .mutate
         .upsert(category_request.into_request())
         .await?
-        .into_inner()
-        .category
-        .unwrap();
+        .into_inner();
+
+    assert!(response.inserted);
 
     let getter = GetCategoryRequest {
-        ap_id: response.ap_id,
+        ap_id: response.category.unwrap().ap_id,
+        ..Default::default()
     }
     .into_request();
 
@@ -73,6 +80,7 @@ async fn upsert_category_update(pool: PgPool) -> Result<()> {
 
     let getter = GetCategoryRequest {
         ap_id: response.ap_id,
+        ..Default::default()
     }
     .into_request();
 
@@ -88,3 +96,278 @@ async fn upsert_category_update(pool: PgPool) -> Result<()> {
 
     Ok(())
 }
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_update_keeps_id(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetCategoryByIdRequest {
+        id: "1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let existing = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    let category = Category {
+        name: "Electro-test".into(),
+        ap_id: existing.ap_id.clone(),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app
+        .mutate
+        .upsert(category_request.into_request())
+        .await?
+        .into_inner();
+
+    assert!(!response.inserted);
+
+    let category = response.category.unwrap();
+    assert_eq!(category.id, existing.id);
+    assert_eq!(category.created_at, existing.created_at);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_missing_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Electro-test".into(),
+        ap_id: "http://localhost/category/cat1".into(),
+        parent_id: Some("http://localhost/category/missing".into()),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app.mutate.upsert(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_change_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let category = Category {
+        name: "Mobile Phones".into(),
+        ap_id: ap_id.clone(),
+        parent_id: Some("http://localhost/category/cat2".into()),
+        sub_categories: vec!["http://localhost/category/item1".into()],
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    app.mutate
+        .upsert(category_request.into_request())
+        .await?
+        .into_inner();
+
+    let mut parents = Vec::with_capacity(2);
+    for id in ["1", "2"] {
+        let getter = GetCategoryByIdRequest {
+            id: id.to_string(),
+            ..Default::default()
+        }
+        .into_request();
+
+        let parent = app
+            .query
+            .category_by_id(getter)
+            .await?
+            .into_inner()
+            .category
+            .unwrap();
+        parents.push(parent);
+    }
+
+    assert!(!parents[0].sub_categories.contains(&ap_id));
+    assert!(parents[1].sub_categories.contains(&ap_id));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_drop_sub_category(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Mobile Phones".into(),
+        ap_id: "http://localhost/category/subcat1".into(),
+        parent_id: Some("http://localhost/category/cat1".into()),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    app.mutate
+        .upsert(category_request.into_request())
+        .await?
+        .into_inner();
+
+    let getter = GetCategoryByIdRequest {
+        id: "12".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let child = app
+        .query
+        .category_by_id(getter)
+        .await?
+        .into_inner()
+        .category
+        .unwrap();
+
+    assert!(child.parent_id.is_none());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_cycle(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Electronics".into(),
+        ap_id: "http://localhost/category/cat1".into(),
+        parent_id: Some("http://localhost/category/item1".into()),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app.mutate.upsert(category_request.into_request()).await;
+
+    let err = response.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+    assert!(err.message().contains(
+        "http://localhost/category/cat1 -> http://localhost/category/item1 -> http://localhost/category/subcat1 -> http://localhost/category/cat1"
+    ));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_own_parent(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Electronics".into(),
+        ap_id: "http://localhost/category/cat1".into(),
+        parent_id: Some("http://localhost/category/cat1".into()),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+
+    let response = app.mutate.upsert(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_stale_version(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category = Category {
+        name: "Electro-test".into(),
+        ap_id: "http://localhost/category/cat1".into(),
+        local: true,
+        ..Default::default()
+    };
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(category),
+        expected_updated_at: Some(Timestamp {
+            seconds: 0,
+            nanos: 0,
+        }),
+        ..Default::default()
+    };
+
+    let response = app.mutate.upsert(category_request.into_request()).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::Aborted);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn upsert_category_deleted(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let delete_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.clone())),
+        expected_updated_at: None,
+    };
+    app.mutate.delete(delete_request.into_request()).await?;
+
+    let category_request = UpsertCategoryRequest {
+        category: Some(Category {
+            name: "Mobile Phones".into(),
+            ap_id: ap_id.clone(),
+            parent_id: Some("http://localhost/category/cat1".into()),
+            local: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let response = app.mutate.upsert(category_request.into_request()).await;
+
+    // only restore brings a deleted category back
+    assert_eq!(response.unwrap_err().code(), Code::FailedPrecondition);
+
+    let getter = GetCategoryRequest {
+        ap_id,
+        ..Default::default()
+    }
+    .into_request();
+    let response = app.query.category_by_ap_id(getter).await;
+    assert_eq!(response.unwrap_err().code(), Code::NotFound);
+
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use categories_service::outbox::{self, CategoryEvent, CategoryEventKind, MemorySink, OutboxSink};
+use sellershut_core::{
+    categories::{
+        Category, CreateCategoryRequest, DeleteCategoryRequest, UpdateCategoryRequest,
+        delete_category_request::Identifier,
+    },
+    google::protobuf::FieldMask,
+};
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+struct FailingSink;
+
+#[tonic::async_trait]
+impl OutboxSink for FailingSink {
+    async fn publish(&self, _event: &CategoryEvent) -> anyhow::Result<()> {
+        anyhow::bail!("sink is unavailable")
+    }
+}
+
+/// Waits for the relay to publish at least `count` events
+async fn wait_for_events(app: &TestApp, count: usize) -> Vec<CategoryEvent> {
+    for _ in 0..100 {
+        let events = app.events.events();
+        if events.len() >= count {
+            return events;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("expected {count} events, got {:?}", app.events.events());
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn outbox_publishes_mutations(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/new";
+
+    let category_request = CreateCategoryRequest {
+        category: Some(Category {
+            name: "Something".into(),
+            ap_id: ap_id.into(),
+            local: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    app.mutate.create(category_request.into_request()).await?;
+
+    let category_request = UpdateCategoryRequest {
+        ap_id: ap_id.into(),
+        category: Some(Category {
+            name: "Something Else".into(),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    app.mutate.update(category_request.into_request()).await?;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.into())),
+        expected_updated_at: None,
+    };
+
+    app.mutate.delete(category_request.into_request()).await?;
+
+    let events = wait_for_events(&app, 3).await;
+
+    let kinds: Vec<_> = events.iter().map(|event| event.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            CategoryEventKind::Created,
+            CategoryEventKind::Updated,
+            CategoryEventKind::Deleted
+        ]
+    );
+    assert!(events.iter().all(|event| event.ap_id == ap_id));
+    assert_eq!(events[1].payload["name"], "Something Else");
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn outbox_publishes_deleted_subtree(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId("http://localhost/category/cat1".into())),
+        expected_updated_at: None,
+    };
+
+    app.mutate.delete(category_request.into_request()).await?;
+
+    // cat1, its 3 sub-categories and their 3 items
+    let events = wait_for_events(&app, 7).await;
+
+    assert_eq!(events.len(), 7);
+    assert!(
+        events
+            .iter()
+            .all(|event| event.kind == CategoryEventKind::Deleted)
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn outbox_publishes_parent_changes(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/new";
+    let parent_id = "http://localhost/category/cat1";
+
+    let category_request = CreateCategoryRequest {
+        category: Some(Category {
+            name: "Something".into(),
+            ap_id: ap_id.into(),
+            parent_id: Some(parent_id.into()),
+            local: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    app.mutate.create(category_request.into_request()).await?;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.into())),
+        expected_updated_at: None,
+    };
+
+    app.mutate.delete(category_request.into_request()).await?;
+
+    let events = wait_for_events(&app, 4).await;
+
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|event| (event.kind, event.ap_id.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (CategoryEventKind::Created, ap_id),
+            (CategoryEventKind::Updated, parent_id),
+            (CategoryEventKind::Deleted, ap_id),
+            (CategoryEventKind::Updated, parent_id)
+        ]
+    );
+
+    // the parent's payload lists the child only while it exists
+    let listed = |event: &CategoryEvent| {
+        event.payload["sub_categories"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|sub| sub == ap_id)
+    };
+    assert!(listed(&events[1]));
+    assert!(!listed(&events[3]));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn outbox_retries_failed_publish(pool: PgPool) -> Result<()> {
+    let mut conn = pool.acquire().await?;
+    outbox::enqueue(
+        &mut conn,
+        CategoryEventKind::Updated,
+        &["http://localhost/category/cat1".to_string()],
+    )
+    .await?;
+    drop(conn);
+
+    let published = outbox::publish_pending(&pool, &FailingSink).await?;
+    assert_eq!(published, 0);
+
+    let sink = MemorySink::default();
+    let published = outbox::publish_pending(&pool, &sink).await?;
+    assert_eq!(published, 1);
+    assert_eq!(sink.events()[0].ap_id, "http://localhost/category/cat1");
+
+    let published = outbox::publish_pending(&pool, &sink).await?;
+    assert_eq!(published, 0);
+
+    Ok(())
+}
@@ -1,3 +1,15 @@
+mod ancestors;
+mod autocomplete;
+mod autocomplete_bench;
+mod by_external_id;
 mod by_id;
+mod by_ids;
 mod categories;
+mod descendants;
+mod history;
+mod origin;
+mod pagination_bench;
+mod search;
+mod sort;
 mod sub_categories;
+mod watch;
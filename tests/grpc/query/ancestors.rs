@@ -0,0 +1,58 @@
+use anyhow::Result;
+use sellershut_core::categories::GetAncestorsRequest;
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn ancestors(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetAncestorsRequest {
+        ap_id: "http://localhost/category/item1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.ancestors(getter).await?.into_inner();
+
+    let ids: Vec<_> = response.categories.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["12", "4", "1"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn ancestors_root(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetAncestorsRequest {
+        ap_id: "http://localhost/category/cat1".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.ancestors(getter).await?.into_inner();
+
+    assert_eq!(response.categories.len(), 1);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn ancestors_missing(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetAncestorsRequest {
+        ap_id: "http://localhost/category/missing".to_string(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.ancestors(getter).await?.into_inner();
+
+    assert!(response.categories.is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use sellershut_core::{
+    categories::{
+        AutocompleteRequest, AutocompleteResponse, Category, RecordCategorySelectionsRequest,
+        UpdateCategoryRequest,
+    },
+    google::protobuf::FieldMask,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+fn autocomplete(prefix: &str) -> AutocompleteRequest {
+    AutocompleteRequest {
+        prefix: prefix.into(),
+        limit: None,
+        ..Default::default()
+    }
+}
+
+fn names(response: &AutocompleteResponse) -> Vec<&str> {
+    response
+        .suggestions
+        .iter()
+        .map(|suggestion| suggestion.category.as_ref().unwrap().name.as_str())
+        .collect()
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn autocomplete_with_breadcrumbs(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let response = app
+        .query
+        .autocomplete(autocomplete("LAP").into_request())
+        .await?
+        .into_inner();
+
+    assert_eq!(names(&response), ["Laptops", "Gaming Laptops"]);
+
+    let breadcrumbs: Vec<_> = response.suggestions[1]
+        .breadcrumbs
+        .iter()
+        .map(|breadcrumb| breadcrumb.name.as_str())
+        .collect();
+    assert_eq!(breadcrumbs, ["Electronics", "Laptops"]);
+    assert_eq!(
+        response.suggestions[0].breadcrumbs[0].ap_id,
+        "http://localhost/category/cat1"
+    );
+
+    let response = app
+        .query
+        .autocomplete(autocomplete("gaming la").into_request())
+        .await?
+        .into_inner();
+    assert_eq!(names(&response), ["Gaming Laptops"]);
+
+    // only local categories are suggested
+    let response = app
+        .query
+        .autocomplete(autocomplete("fic").into_request())
+        .await?
+        .into_inner();
+    assert!(response.suggestions.is_empty());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn autocomplete_ranks_suggestions(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    // whole names first, then shallower categories, then by name
+    let response = app
+        .query
+        .autocomplete(autocomplete("t").into_request())
+        .await?
+        .into_inner();
+    assert_eq!(
+        names(&response),
+        ["T-Shirts", "Televisions", "4K TVs", "Nike T-Shirts"]
+    );
+
+    let request = RecordCategorySelectionsRequest {
+        ap_ids: vec![
+            "http://localhost/category/subcat3".into(),
+            "http://localhost/category/item4".into(),
+            "http://localhost/category/subcat3".into(),
+        ],
+    };
+    app.mutate.record_selections(request.into_request()).await?;
+
+    let response = app
+        .query
+        .autocomplete(
+            AutocompleteRequest {
+                prefix: "t".into(),
+                limit: Some(3),
+                ..Default::default()
+            }
+            .into_request(),
+        )
+        .await?
+        .into_inner();
+    assert_eq!(
+        names(&response),
+        ["Televisions", "T-Shirts", "Nike T-Shirts"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn autocomplete_follows_moved_categories(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    // televisions, and the 4K TVs beneath them, move under T-Shirts
+    let request = UpdateCategoryRequest {
+        ap_id: "http://localhost/category/subcat3".into(),
+        category: Some(Category {
+            parent_id: Some("http://localhost/category/subcat4".into()),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["parent_id".into()],
+        }),
+        expected_updated_at: None,
+    };
+    app.mutate.update(request.into_request()).await?;
+
+    let response = app
+        .query
+        .autocomplete(autocomplete("4k").into_request())
+        .await?
+        .into_inner();
+    let breadcrumbs: Vec<_> = response.suggestions[0]
+        .breadcrumbs
+        .iter()
+        .map(|breadcrumb| breadcrumb.name.as_str())
+        .collect();
+    assert_eq!(breadcrumbs, ["Clothing", "T-Shirts", "Televisions"]);
+
+    // both now sit a level deeper, so rank below the shallower Nike T-Shirts
+    let response = app
+        .query
+        .autocomplete(autocomplete("t").into_request())
+        .await?
+        .into_inner();
+    assert_eq!(
+        names(&response),
+        ["T-Shirts", "Televisions", "Nike T-Shirts", "4K TVs"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn autocomplete_validates_input(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let status = app
+        .query
+        .autocomplete(autocomplete("  ").into_request())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let request = AutocompleteRequest {
+        prefix: "t".into(),
+        limit: Some(0),
+        ..Default::default()
+    };
+    let status = app
+        .query
+        .autocomplete(request.into_request())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // wildcards are matched literally
+    let response = app
+        .query
+        .autocomplete(autocomplete("%").into_request())
+        .await?
+        .into_inner();
+    assert!(response.suggestions.is_empty());
+
+    Ok(())
+}
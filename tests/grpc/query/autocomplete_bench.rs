@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use sellershut_core::categories::AutocompleteRequest;
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+/// Median time to suggest categories for `prefix`
+async fn time_prefix(app: &mut TestApp, prefix: &str) -> Result<Duration> {
+    let mut timings = Vec::new();
+    for _ in 0..9 {
+        let request = AutocompleteRequest {
+            prefix: prefix.into(),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let response = app.query.autocomplete(request.into_request()).await?;
+        timings.push(started.elapsed());
+
+        assert!(!response.into_inner().suggestions.is_empty());
+    }
+
+    timings.sort();
+    Ok(timings[timings.len() / 2])
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+#[ignore = "benchmark, run with `cargo test -- --ignored`"]
+async fn autocomplete_latency_at_50k(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    sqlx::query!(
+        "
+        insert into category (id, ap_id, local, name, sub_categories, created_at)
+        select
+            'bench' || g,
+            'http://localhost/category/bench' || g,
+            true,
+            'Bench ' || g,
+            '{}',
+            now() - make_interval(secs => g)
+        from generate_series(1, 50000) g"
+    )
+    .execute(&app.state.services.postgres)
+    .await?;
+    sqlx::query!("analyze category, category_rank")
+        .execute(&app.state.services.postgres)
+        .await?;
+
+    // a prefix most names start with, one a single name starts with, and one
+    // only later words start with
+    let mut timings = Vec::new();
+    for prefix in ["b", "be", "bench 49999", "1"] {
+        timings.push(time_prefix(&mut app, prefix).await?);
+    }
+
+    assert!(
+        timings
+            .iter()
+            .all(|timing| *timing < Duration::from_millis(10)),
+        "{timings:?}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use sellershut_core::categories::{
+    ExternalId, ExternalTaxonomy, GetCategoryByExternalIdRequest, SetExternalIdsRequest,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn category_by_external_id(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let google = ExternalId {
+        taxonomy: ExternalTaxonomy::Google.into(),
+        external_id: "328".into(),
+    };
+
+    let request = SetExternalIdsRequest {
+        ap_id: "http://localhost/category/item2".into(),
+        external_ids: vec![google.clone()],
+    };
+    app.mutate.set_external_ids(request.into_request()).await?;
+
+    let getter = GetCategoryByExternalIdRequest {
+        external_id: Some(google.clone()),
+        ..Default::default()
+    }
+    .into_request();
+    let response = app
+        .query
+        .category_by_external_id(getter)
+        .await?
+        .into_inner();
+
+    assert_eq!(response.category.unwrap().name, "Gaming Laptops");
+    assert_eq!(response.external_ids, [google]);
+
+    // the same id in another taxonomy is a different category
+    let getter = GetCategoryByExternalIdRequest {
+        external_id: Some(ExternalId {
+            taxonomy: ExternalTaxonomy::Ebay.into(),
+            external_id: "328".into(),
+        }),
+        ..Default::default()
+    }
+    .into_request();
+    let response = app
+        .query
+        .category_by_external_id(getter)
+        .await?
+        .into_inner();
+
+    assert!(response.category.is_none());
+
+    let getter = GetCategoryByExternalIdRequest {
+        external_id: None,
+        ..Default::default()
+    }
+    .into_request();
+    let status = app.query.category_by_external_id(getter).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    Ok(())
+}
@@ -11,6 +11,7 @@ async fn category_by_ap_id(pool: PgPool) -> Result<()> {
 
     let getter = GetCategoryRequest {
         ap_id: "http://localhost/category/cat1".to_string(),
+        ..Default::default()
     }
     .into_request();
 
@@ -32,6 +33,7 @@ async fn category_by_id(pool: PgPool) -> Result<()> {
 
     let getter = GetCategoryByIdRequest {
         id: "1".to_string(),
+        ..Default::default()
     }
     .into_request();
 
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use sellershut_core::categories::{
+    DeleteCategoryRequest, GetCategoriesByApIdsRequest, GetCategoriesByIdsRequest,
+    delete_category_request::Identifier,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_ids(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetCategoriesByIdsRequest {
+        ids: vec!["3".into(), "nope".into(), "1".into(), "3".into()],
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.categories_by_ids(getter).await?.into_inner();
+
+    let ids: Vec<_> = response.categories.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["3", "1"]);
+    assert_eq!(response.missing, ["nope"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_ids_deleted_missing(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::Id("12".into())),
+        expected_updated_at: None,
+    };
+
+    app.mutate.delete(category_request.into_request()).await?;
+
+    let getter = GetCategoriesByIdsRequest {
+        ids: vec!["12".into(), "13".into()],
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.categories_by_ids(getter).await?.into_inner();
+
+    assert_eq!(response.categories.len(), 1);
+    assert_eq!(response.categories[0].id, "13");
+    assert_eq!(response.missing, ["12"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_ap_ids(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetCategoriesByApIdsRequest {
+        ap_ids: vec![
+            "http://localhost/category/item2".into(),
+            "http://localhost/category/missing".into(),
+            "http://localhost/category/cat1".into(),
+        ],
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.categories_by_ap_ids(getter).await?.into_inner();
+
+    let ids: Vec<_> = response.categories.iter().map(|c| c.id.as_str()).collect();
+    assert_eq!(ids, ["13", "1"]);
+    assert_eq!(response.missing, ["http://localhost/category/missing"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_ids_too_many(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetCategoriesByIdsRequest {
+        ids: (0..=100).map(|i| i.to_string()).collect(),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.categories_by_ids(getter).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    Ok(())
+}
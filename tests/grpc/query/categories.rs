@@ -1,14 +1,47 @@
 use anyhow::Result;
 
-use sellershut_core::common::pagination::{
-    Cursor,
-    cursor::{self, CursorValue, Index, cursor_value::CursorType},
+use sellershut_core::{
+    categories::{
+        CategorySort, GetCategoriesRequest, Origin, OriginScope, SortDirection, origin::Filter,
+    },
+    common::pagination::{
+        Cursor,
+        cursor::{self, CursorValue, Index, cursor_value::CursorType},
+    },
 };
 use sqlx::PgPool;
 use tonic::IntoRequest;
 
 use crate::helpers::TestApp;
 
+fn request(pagination: Cursor) -> tonic::Request<GetCategoriesRequest> {
+    GetCategoriesRequest {
+        pagination: Some(pagination),
+        ..Default::default()
+    }
+    .into_request()
+}
+
+fn newest_first(index: Index, cursor: Option<CursorType>) -> GetCategoriesRequest {
+    GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: cursor.map(|cursor| CursorValue {
+                cursor_type: Some(cursor),
+            }),
+            index: Some(index),
+        }),
+        sort: Some(CategorySort {
+            direction: SortDirection::Desc.into(),
+            ..Default::default()
+        }),
+        // remote categories too, so there are 18 of them
+        origin: Some(Origin {
+            filter: Some(Filter::Scope(OriginScope::All.into())),
+        }),
+        ..Default::default()
+    }
+}
+
 #[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
 async fn all_last(pool: PgPool) -> Result<()> {
     let mut app = TestApp::new(pool).await;
@@ -16,10 +49,9 @@ async fn all_last(pool: PgPool) -> Result<()> {
     let getter = Cursor {
         cursor_value: None,
         index: Some(Index::Last(2)),
-    }
-    .into_request();
+    };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
 
     assert_eq!(response.edges.len(), 2);
 
@@ -33,10 +65,9 @@ async fn no_parent_first(pool: PgPool) -> Result<()> {
     let getter = Cursor {
         cursor_value: None,
         index: Some(Index::First(2)),
-    }
-    .into_request();
+    };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
 
     assert_eq!(response.edges.len(), 2);
 
@@ -50,10 +81,9 @@ async fn no_parent_last_exact(pool: PgPool) -> Result<()> {
     let getter = Cursor {
         cursor_value: None,
         index: Some(Index::Last(2)),
-    }
-    .into_request();
+    };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
 
     assert_eq!(response.edges.len(), 2);
 
@@ -67,10 +97,9 @@ async fn cursor_last(pool: PgPool) -> Result<()> {
     let getter = Cursor {
         cursor_value: None,
         index: Some(Index::Last(2)),
-    }
-    .into_request();
+    };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
     dbg!(&response);
 
     assert_eq!(response.edges.len(), 2);
@@ -83,7 +112,7 @@ async fn cursor_last(pool: PgPool) -> Result<()> {
         index: Some(cursor::Index::Last(1)),
     };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
     assert_eq!(response.edges.len(), 1);
     Ok(())
 }
@@ -95,10 +124,9 @@ async fn no_index(pool: PgPool) -> Result<()> {
     let getter = Cursor {
         cursor_value: None,
         index: None,
-    }
-    .into_request();
+    };
 
-    let response = app.query.categories(getter).await;
+    let response = app.query.categories(request(getter)).await;
 
     // no pagination index
     assert!(response.is_err());
@@ -113,10 +141,9 @@ async fn cursor_first_sub(pool: PgPool) -> Result<()> {
     let getter = Cursor {
         cursor_value: None,
         index: Some(Index::First(2)),
-    }
-    .into_request();
+    };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
 
     assert_eq!(response.edges.len(), 2);
 
@@ -129,8 +156,57 @@ async fn cursor_first_sub(pool: PgPool) -> Result<()> {
         index: Some(cursor::Index::First(1)),
     };
 
-    let response = app.query.categories(getter).await?.into_inner();
+    let response = app.query.categories(request(getter)).await?.into_inner();
     assert_eq!(response.edges.len(), 1);
 
     Ok(())
 }
+
+/// Walks the 18 categories six at a time both ways, so that every page ends
+/// exactly where another begins
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn page_info_at_exact_multiple(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let mut cursor = None;
+    for (has_next_page, has_previous_page) in [(true, false), (true, true), (false, true)] {
+        let response = app
+            .query
+            .categories(newest_first(Index::First(6), cursor.take()).into_request())
+            .await?
+            .into_inner();
+        let page_info = response.page_info.unwrap();
+
+        assert_eq!(response.edges.len(), 6);
+        assert_eq!(page_info.has_next_page, has_next_page);
+        assert_eq!(page_info.has_previous_page, has_previous_page);
+        cursor = Some(CursorType::After(page_info.end_cursor.unwrap()));
+    }
+
+    let response = app
+        .query
+        .categories(newest_first(Index::First(6), cursor).into_request())
+        .await?
+        .into_inner();
+    let page_info = response.page_info.unwrap();
+    assert!(response.edges.is_empty());
+    assert!(!page_info.has_next_page);
+    assert!(page_info.has_previous_page);
+
+    let mut cursor = None;
+    for (has_next_page, has_previous_page) in [(false, true), (true, true), (true, false)] {
+        let response = app
+            .query
+            .categories(newest_first(Index::Last(6), cursor.take()).into_request())
+            .await?
+            .into_inner();
+        let page_info = response.page_info.unwrap();
+
+        assert_eq!(response.edges.len(), 6);
+        assert_eq!(page_info.has_next_page, has_next_page);
+        assert_eq!(page_info.has_previous_page, has_previous_page);
+        cursor = Some(CursorType::Before(page_info.end_cursor.unwrap()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use anyhow::Result;
+use sellershut_core::categories::GetDescendantsRequest;
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn descendants(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetDescendantsRequest {
+        ap_id: "http://localhost/category/cat1".to_string(),
+        max_depth: None,
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.descendants(getter).await?.into_inner();
+
+    let depths: Vec<_> = response.descendants.iter().map(|d| d.depth).collect();
+    assert_eq!(depths, [1, 1, 1, 2, 2, 2]);
+
+    let mobile_phones = response.descendants[0].category.as_ref().unwrap();
+    assert_eq!(mobile_phones.id, "4");
+    assert_eq!(mobile_phones.sub_categories.len(), 1);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn descendants_max_depth(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetDescendantsRequest {
+        ap_id: "http://localhost/category/cat1".to_string(),
+        max_depth: Some(1),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.descendants(getter).await?.into_inner();
+
+    assert_eq!(response.descendants.len(), 3);
+    assert!(response.descendants.iter().all(|d| d.depth == 1));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn descendants_invalid_depth(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetDescendantsRequest {
+        ap_id: "http://localhost/category/cat1".to_string(),
+        max_depth: Some(0),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.descendants(getter).await;
+
+    assert_eq!(response.unwrap_err().code(), Code::InvalidArgument);
+
+    Ok(())
+}
@@ -0,0 +1,319 @@
+use anyhow::Result;
+use sellershut_core::{
+    categories::{
+        BulkUpsertCategoriesRequest, Category, CategoryHistoryEntry, CreateCategoryRequest,
+        DeleteCategoryRequest, GetCategoryHistoryRequest, UpdateCategoryRequest,
+        delete_category_request::Identifier,
+    },
+    common::pagination::{
+        Cursor,
+        cursor::{CursorValue, Index, cursor_value::CursorType},
+    },
+    google::protobuf::FieldMask,
+};
+use sqlx::PgPool;
+use tonic::{IntoRequest, Request};
+
+use crate::helpers::{TestApp, rename};
+
+fn with_actor<T>(message: T, actor: &str) -> Request<T> {
+    let mut request = message.into_request();
+    request
+        .metadata_mut()
+        .insert("x-actor", actor.parse().unwrap());
+    request
+}
+
+fn history_request(
+    ap_id: &str,
+    index: Index,
+    cursor_type: Option<CursorType>,
+) -> Request<GetCategoryHistoryRequest> {
+    GetCategoryHistoryRequest {
+        ap_id: ap_id.into(),
+        pagination: Some(Cursor {
+            cursor_value: cursor_type.map(|cursor_type| CursorValue {
+                cursor_type: Some(cursor_type),
+            }),
+            index: Some(index),
+        }),
+    }
+    .into_request()
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn history_records_changes(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/new";
+
+    let category_request = CreateCategoryRequest {
+        category: Some(Category {
+            name: "Something".into(),
+            ap_id: ap_id.into(),
+            local: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    app.mutate
+        .create(with_actor(category_request, "alice"))
+        .await?;
+
+    app.mutate
+        .update(with_actor(rename(ap_id, "Something Else"), "bob"))
+        .await?;
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.into())),
+        expected_updated_at: None,
+    };
+
+    // no actor given
+    app.mutate.delete(category_request.into_request()).await?;
+
+    let response = app
+        .query
+        .history(history_request(ap_id, Index::First(10), None))
+        .await?
+        .into_inner();
+
+    let entries: Vec<_> = response
+        .edges
+        .into_iter()
+        .map(|edge| edge.node.unwrap())
+        .collect();
+
+    let operations: Vec<_> = entries.iter().map(|e| e.operation.as_str()).collect();
+    assert_eq!(operations, ["create", "update", "delete"]);
+
+    let actors: Vec<_> = entries.iter().map(|e| e.actor.as_deref()).collect();
+    assert_eq!(actors, [Some("alice"), Some("bob"), None]);
+
+    assert!(entries[0].before.is_none());
+    assert_eq!(entries[0].after.as_ref().unwrap().name, "Something");
+
+    assert_eq!(entries[1].before.as_ref().unwrap().name, "Something");
+    assert_eq!(entries[1].after.as_ref().unwrap().name, "Something Else");
+
+    assert!(entries[2].before.is_some());
+    assert!(entries[2].after.is_some());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn history_records_deleted_subtree(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1";
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.into())),
+        expected_updated_at: None,
+    };
+
+    app.mutate
+        .delete(with_actor(category_request, "alice"))
+        .await?;
+
+    // a descendant deleted along with it
+    let response = app
+        .query
+        .history(history_request(
+            "http://localhost/category/item1",
+            Index::First(10),
+            None,
+        ))
+        .await?
+        .into_inner();
+
+    assert_eq!(response.edges.len(), 1);
+    let entry = response.edges[0].node.as_ref().unwrap();
+    assert_eq!(entry.operation, "delete");
+    assert_eq!(entry.actor.as_deref(), Some("alice"));
+    assert_eq!(entry.before.as_ref().unwrap().name, "Smartphones");
+    assert!(entry.after.is_some());
+
+    // the parent that stopped listing it
+    let response = app
+        .query
+        .history(history_request(
+            "http://localhost/category/cat1",
+            Index::First(10),
+            None,
+        ))
+        .await?
+        .into_inner();
+
+    assert_eq!(response.edges.len(), 1);
+    let entry = response.edges[0].node.as_ref().unwrap();
+    assert_eq!(entry.operation, "update");
+    assert_eq!(entry.actor.as_deref(), Some("alice"));
+    assert!(
+        entry
+            .before
+            .as_ref()
+            .unwrap()
+            .sub_categories
+            .contains(&ap_id.to_string())
+    );
+    assert!(
+        !entry
+            .after
+            .as_ref()
+            .unwrap()
+            .sub_categories
+            .contains(&ap_id.to_string())
+    );
+
+    Ok(())
+}
+
+/// The only history entry of a category
+async fn only_entry(app: &mut TestApp, ap_id: &str) -> Result<CategoryHistoryEntry> {
+    let response = app
+        .query
+        .history(history_request(ap_id, Index::First(10), None))
+        .await?
+        .into_inner();
+
+    assert_eq!(response.edges.len(), 1, "{ap_id}");
+    Ok(response.edges[0].node.clone().unwrap())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn history_records_moved_children(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/subcat1".to_string();
+
+    let category_request = UpdateCategoryRequest {
+        ap_id: ap_id.clone(),
+        category: Some(Category {
+            parent_id: Some("http://localhost/category/cat2".into()),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["parent_id".into()],
+        }),
+        expected_updated_at: None,
+    };
+
+    app.mutate
+        .update(with_actor(category_request, "alice"))
+        .await?;
+
+    // the parent it left
+    let entry = only_entry(&mut app, "http://localhost/category/cat1").await?;
+    assert_eq!(entry.operation, "update");
+    assert_eq!(entry.actor.as_deref(), Some("alice"));
+    assert!(entry.before.unwrap().sub_categories.contains(&ap_id));
+    assert!(!entry.after.unwrap().sub_categories.contains(&ap_id));
+
+    // and the parent it joined
+    let entry = only_entry(&mut app, "http://localhost/category/cat2").await?;
+    assert_eq!(entry.operation, "update");
+    assert_eq!(entry.actor.as_deref(), Some("alice"));
+    assert!(!entry.before.unwrap().sub_categories.contains(&ap_id));
+    assert!(entry.after.unwrap().sub_categories.contains(&ap_id));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn history_records_bulk_moved_children(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/new".to_string();
+
+    let request = BulkUpsertCategoriesRequest {
+        categories: vec![Category {
+            name: "Poetry".into(),
+            ap_id: ap_id.clone(),
+            parent_id: Some("http://localhost/category/cat3".into()),
+            local: true,
+            ..Default::default()
+        }],
+        all_or_nothing: true,
+    };
+
+    app.mutate.bulk_upsert(with_actor(request, "alice")).await?;
+
+    let entry = only_entry(&mut app, "http://localhost/category/cat3").await?;
+    assert_eq!(entry.operation, "update");
+    assert_eq!(entry.actor.as_deref(), Some("alice"));
+    assert!(!entry.before.unwrap().sub_categories.contains(&ap_id));
+    assert!(entry.after.unwrap().sub_categories.contains(&ap_id));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn history_paginates(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/item7";
+
+    for name in ["First", "Second", "Third"] {
+        app.mutate
+            .update(rename(ap_id, name).into_request())
+            .await?;
+    }
+
+    let response = app
+        .query
+        .history(history_request(ap_id, Index::First(2), None))
+        .await?
+        .into_inner();
+
+    let names: Vec<_> = response
+        .edges
+        .iter()
+        .map(|edge| {
+            edge.node
+                .as_ref()
+                .unwrap()
+                .after
+                .as_ref()
+                .unwrap()
+                .name
+                .as_str()
+        })
+        .collect();
+    assert_eq!(names, ["First", "Second"]);
+
+    let page_info = response.page_info.unwrap();
+    assert!(page_info.has_next_page);
+
+    let response = app
+        .query
+        .history(history_request(
+            ap_id,
+            Index::First(2),
+            Some(CursorType::After(page_info.end_cursor.unwrap())),
+        ))
+        .await?
+        .into_inner();
+
+    assert_eq!(response.edges.len(), 1);
+    let entry = response.edges[0].node.as_ref().unwrap();
+    assert_eq!(entry.after.as_ref().unwrap().name, "Third");
+    assert!(!response.page_info.unwrap().has_next_page);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn history_is_append_only(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/item7";
+
+    app.mutate
+        .update(rename(ap_id, "Renamed").into_request())
+        .await?;
+
+    let result = sqlx::query("delete from category_history")
+        .execute(&app.state.services.postgres)
+        .await;
+
+    assert!(result.is_err());
+
+    Ok(())
+}
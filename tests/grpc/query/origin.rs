@@ -0,0 +1,285 @@
+use anyhow::Result;
+use sellershut_core::{
+    categories::{
+        AutocompleteRequest, Category, GetAncestorsRequest, GetCategoriesByApIdsRequest,
+        GetCategoriesRequest, GetCategoryByIdRequest, GetCategoryRequest, GetDescendantsRequest,
+        GetSubCategoriesRequest, Origin, OriginScope, SearchCategoriesRequest,
+        UpsertCategoryRequest, origin::Filter,
+    },
+    common::pagination::{Cursor, cursor::Index},
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+fn scope(scope: OriginScope) -> Option<Origin> {
+    Some(Origin {
+        filter: Some(Filter::Scope(scope.into())),
+    })
+}
+
+fn host(host: &str) -> Option<Origin> {
+    Some(Origin {
+        filter: Some(Filter::Host(host.into())),
+    })
+}
+
+fn categories(origin: Option<Origin>) -> GetCategoriesRequest {
+    GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(20)),
+        }),
+        origin,
+        ..Default::default()
+    }
+}
+
+async fn create_remote(app: &mut TestApp) -> Result<()> {
+    let category = Category {
+        name: "Garden".into(),
+        ap_id: "https://Remote.example/category/garden".into(),
+        local: false,
+        ..Default::default()
+    };
+    let request = UpsertCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+    app.mutate.upsert(request.into_request()).await?;
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_origin(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    create_remote(&mut app).await?;
+
+    let query = app.query.clone();
+    let count = |origin| {
+        let mut query = query.clone();
+        async move {
+            let response = query
+                .categories(categories(origin).into_request())
+                .await?
+                .into_inner();
+            anyhow::Ok(response.edges.len())
+        }
+    };
+
+    assert_eq!(count(None).await?, 14);
+    assert_eq!(count(scope(OriginScope::Local)).await?, 14);
+    assert_eq!(count(scope(OriginScope::Remote)).await?, 5);
+    assert_eq!(count(scope(OriginScope::All)).await?, 19);
+
+    let response = app
+        .query
+        .categories(categories(host("REMOTE.example")).into_request())
+        .await?
+        .into_inner();
+    let names: Vec<_> = response
+        .edges
+        .iter()
+        .map(|edge| edge.node.as_ref().unwrap().name.as_str())
+        .collect();
+    assert_eq!(names, ["Garden"]);
+
+    // local and remote categories alike live on localhost in the fixtures
+    assert_eq!(count(host("localhost")).await?, 18);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn sub_categories_by_origin(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    // Fiction and Non-Fiction are remote
+    let request = |origin| GetSubCategoriesRequest {
+        id: Some("http://localhost/category/cat3".into()),
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(5)),
+        }),
+        include_roots: false,
+        origin,
+        ..Default::default()
+    };
+
+    let response = app
+        .query
+        .sub_categories(request(None).into_request())
+        .await?
+        .into_inner();
+    assert!(response.edges.is_empty());
+
+    let response = app
+        .query
+        .sub_categories(request(scope(OriginScope::Remote)).into_request())
+        .await?
+        .into_inner();
+    assert_eq!(response.edges.len(), 2);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn lookups_by_origin(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    create_remote(&mut app).await?;
+    let ap_id = "https://Remote.example/category/garden";
+
+    let request = GetCategoryRequest {
+        ap_id: ap_id.into(),
+        origin: None,
+    };
+    let response = app.query.category_by_ap_id(request.into_request()).await?;
+    assert!(response.into_inner().category.is_none());
+
+    let request = GetCategoryRequest {
+        ap_id: ap_id.into(),
+        origin: host("remote.example"),
+    };
+    let response = app.query.category_by_ap_id(request.into_request()).await?;
+    assert!(response.into_inner().category.is_some());
+
+    let request = GetCategoriesByApIdsRequest {
+        ap_ids: vec![ap_id.into(), "http://localhost/category/cat1".into()],
+        origin: scope(OriginScope::Remote),
+    };
+    let response = app
+        .query
+        .categories_by_ap_ids(request.into_request())
+        .await?
+        .into_inner();
+    assert_eq!(response.categories.len(), 1);
+    assert_eq!(response.missing, ["http://localhost/category/cat1"]);
+
+    // ids are ours, so they find remote categories unless told otherwise
+    let request = GetCategoryByIdRequest {
+        id: "10".into(),
+        origin: None,
+    };
+    let response = app.query.category_by_id(request.into_request()).await?;
+    assert!(response.into_inner().category.is_some());
+
+    let request = GetCategoryByIdRequest {
+        id: "10".into(),
+        origin: scope(OriginScope::Local),
+    };
+    let response = app.query.category_by_id(request.into_request()).await?;
+    assert!(response.into_inner().category.is_none());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn hierarchy_and_search_by_origin(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    // Mystery Novels sits beneath the remote Fiction
+    let request = |origin| GetAncestorsRequest {
+        ap_id: "http://localhost/category/item6".into(),
+        origin,
+    };
+    let response = app
+        .query
+        .ancestors(request(None).into_request())
+        .await?
+        .into_inner();
+    assert!(response.categories.is_empty());
+
+    let response = app
+        .query
+        .ancestors(request(scope(OriginScope::Remote)).into_request())
+        .await?
+        .into_inner();
+    let ids: Vec<_> = response.categories.iter().map(|c| c.id.as_str()).collect();
+    // the walk stops at Books, which is local
+    assert_eq!(ids, ["17", "10"]);
+
+    // Books has only remote categories beneath it
+    let request = |origin| GetDescendantsRequest {
+        ap_id: "http://localhost/category/cat3".into(),
+        max_depth: None,
+        origin,
+    };
+    let response = app
+        .query
+        .descendants(request(None).into_request())
+        .await?
+        .into_inner();
+    assert!(response.descendants.is_empty());
+
+    let response = app
+        .query
+        .descendants(request(scope(OriginScope::All)).into_request())
+        .await?
+        .into_inner();
+    assert_eq!(response.descendants.len(), 4);
+
+    let request = GetDescendantsRequest {
+        ap_id: "http://localhost/category/subcat7".into(),
+        max_depth: None,
+        origin: scope(OriginScope::All),
+    };
+    let response = app
+        .query
+        .descendants(request.into_request())
+        .await?
+        .into_inner();
+    assert_eq!(response.descendants.len(), 1);
+
+    let request = SearchCategoriesRequest {
+        query: "fiction".into(),
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(5)),
+        }),
+        origin: scope(OriginScope::Remote),
+    };
+    let response = app.query.search(request.into_request()).await?.into_inner();
+    assert_eq!(response.edges.len(), 2);
+
+    let request = AutocompleteRequest {
+        prefix: "myst".into(),
+        limit: None,
+        origin: host("localhost"),
+    };
+    let response = app
+        .query
+        .autocomplete(request.into_request())
+        .await?
+        .into_inner();
+    assert_eq!(response.suggestions.len(), 1);
+    assert_eq!(
+        response.suggestions[0].breadcrumbs[1].ap_id,
+        "http://localhost/category/subcat7"
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn origin_is_validated(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    for origin in [
+        host("https://remote.example"),
+        host(" "),
+        Some(Origin {
+            filter: Some(Filter::Scope(7)),
+        }),
+    ] {
+        let status = app
+            .query
+            .categories(categories(origin).into_request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use sellershut_core::{
+    categories::{CategorySort, GetCategoriesRequest, SortDirection},
+    common::pagination::{
+        Cursor,
+        cursor::{CursorValue, Index, cursor_value::CursorType},
+    },
+};
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+fn newest_first(index: Index, cursor: Option<CursorType>) -> GetCategoriesRequest {
+    GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: cursor.map(|cursor| CursorValue {
+                cursor_type: Some(cursor),
+            }),
+            index: Some(index),
+        }),
+        sort: Some(CategorySort {
+            direction: SortDirection::Desc.into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Median time to fetch the page just before the 20th newest category, which
+/// has the rest of the table behind its cursor
+async fn time_page(app: &mut TestApp) -> Result<Duration> {
+    let response = app
+        .query
+        .categories(newest_first(Index::First(20), None).into_request())
+        .await?
+        .into_inner();
+    let cursor = response.page_info.unwrap().end_cursor.unwrap();
+
+    let mut timings = Vec::new();
+    for _ in 0..9 {
+        let request = newest_first(Index::Last(10), Some(CursorType::Before(cursor.clone())));
+        let started = Instant::now();
+        let response = app.query.categories(request.into_request()).await?;
+        timings.push(started.elapsed());
+
+        let response = response.into_inner();
+        assert_eq!(response.edges.len(), 10);
+        assert!(response.page_info.unwrap().has_next_page);
+    }
+
+    timings.sort();
+    Ok(timings[timings.len() / 2])
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+#[ignore = "benchmark, run with `cargo test -- --ignored`"]
+async fn page_latency_is_flat(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let mut timings = Vec::new();
+    let mut inserted = 0;
+    for size in [1_000, 10_000, 100_000] {
+        sqlx::query!(
+            "
+            insert into category (id, ap_id, local, name, sub_categories, created_at)
+            select
+                'bench' || g,
+                'http://localhost/category/bench' || g,
+                true,
+                'Bench ' || g,
+                '{}',
+                now() - make_interval(secs => g)
+            from generate_series($1::int, $2::int) g",
+            inserted + 1,
+            size
+        )
+        .execute(&app.state.services.postgres)
+        .await?;
+        sqlx::query!("analyze category")
+            .execute(&app.state.services.postgres)
+            .await?;
+        inserted = size;
+
+        timings.push(time_page(&mut app).await?);
+    }
+
+    // a hundred times the categories should not take more than a few times as long
+    let (smallest, largest) = (timings[0], timings[timings.len() - 1]);
+    assert!(
+        largest < smallest * 4 + Duration::from_millis(5),
+        "{timings:?}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use sellershut_core::{
+    categories::SearchCategoriesRequest,
+    common::pagination::{
+        Cursor,
+        cursor::{CursorValue, Index, cursor_value::CursorType},
+    },
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+fn search(query: &str, first: i32, after: Option<String>) -> SearchCategoriesRequest {
+    SearchCategoriesRequest {
+        query: query.into(),
+        pagination: Some(Cursor {
+            cursor_value: after.map(|after| CursorValue {
+                cursor_type: Some(CursorType::After(after)),
+            }),
+            index: Some(Index::First(first)),
+        }),
+        ..Default::default()
+    }
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn search_tolerates_typos(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let response = app
+        .query
+        .search(search("electornics", 5, None).into_request())
+        .await?
+        .into_inner();
+
+    let names: Vec<_> = response
+        .edges
+        .iter()
+        .map(|edge| edge.node.as_ref().unwrap().name.as_str())
+        .collect();
+    assert_eq!(names, ["Electronics"]);
+
+    // stemmed, so the plural matches
+    let response = app
+        .query
+        .search(search("phone", 5, None).into_request())
+        .await?
+        .into_inner();
+
+    assert_eq!(
+        response.edges[0].node.as_ref().unwrap().name,
+        "Mobile Phones"
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn search_pages_by_relevance(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let response = app
+        .query
+        .search(search("laptops", 1, None).into_request())
+        .await?
+        .into_inner();
+
+    let page_info = response.page_info.unwrap();
+    assert_eq!(response.edges.len(), 1);
+    assert!(page_info.has_next_page);
+    assert!(!page_info.has_previous_page);
+    let first = response.edges[0].node.clone().unwrap().name;
+
+    let response = app
+        .query
+        .search(search("laptops", 5, page_info.end_cursor).into_request())
+        .await?
+        .into_inner();
+
+    let page_info = response.page_info.unwrap();
+    let rest: Vec<_> = response
+        .edges
+        .iter()
+        .map(|edge| edge.node.as_ref().unwrap().name.as_str())
+        .collect();
+    assert!(!rest.contains(&first.as_str()));
+    assert!(!page_info.has_next_page);
+    assert!(page_info.has_previous_page);
+
+    let mut all = rest;
+    all.push(&first);
+    all.sort();
+    assert_eq!(all, ["Gaming Laptops", "Laptops"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn search_local_only(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    // both fiction categories are remote
+    let response = app
+        .query
+        .search(search("fiction", 5, None).into_request())
+        .await?
+        .into_inner();
+
+    assert!(response.edges.is_empty());
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn search_invalid(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let status = app
+        .query
+        .search(search("  ", 5, None).into_request())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    let mut request = search("laptops", 5, None);
+    request.pagination.as_mut().unwrap().index = Some(Index::Last(5));
+    let status = app.query.search(request.into_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    // a cursor only carries on the search it came from
+    let page_info = app
+        .query
+        .search(search("laptops", 1, None).into_request())
+        .await?
+        .into_inner()
+        .page_info
+        .unwrap();
+    let status = app
+        .query
+        .search(search("phone", 5, page_info.end_cursor).into_request())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    Ok(())
+}
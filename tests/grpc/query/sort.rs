@@ -0,0 +1,200 @@
+use anyhow::Result;
+use sellershut_core::{
+    categories::{
+        Category, CategorySort, CategorySortKey, Connection, GetCategoriesRequest,
+        GetSubCategoriesRequest, SortDirection, UpdateCategoryRequest,
+    },
+    common::pagination::{
+        Cursor,
+        cursor::{CursorValue, Index, cursor_value::CursorType},
+    },
+    google::protobuf::FieldMask,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest};
+
+use crate::helpers::TestApp;
+
+fn sorted(
+    key: CategorySortKey,
+    direction: SortDirection,
+    index: Index,
+    cursor: Option<CursorType>,
+) -> GetCategoriesRequest {
+    GetCategoriesRequest {
+        pagination: Some(Cursor {
+            cursor_value: cursor.map(|cursor| CursorValue {
+                cursor_type: Some(cursor),
+            }),
+            index: Some(index),
+        }),
+        sort: Some(CategorySort {
+            key: key.into(),
+            direction: direction.into(),
+        }),
+        ..Default::default()
+    }
+}
+
+fn names(connection: &Connection) -> Vec<&str> {
+    connection
+        .edges
+        .iter()
+        .map(|edge| edge.node.as_ref().unwrap().name.as_str())
+        .collect()
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_name(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let (name, asc) = (CategorySortKey::Name, SortDirection::Asc);
+
+    let response = app
+        .query
+        .categories(sorted(name, asc, Index::First(3), None).into_request())
+        .await?
+        .into_inner();
+    assert_eq!(names(&response), ["4K TVs", "Books", "Clothing"]);
+
+    let page_info = response.page_info.unwrap();
+    assert!(page_info.has_next_page);
+    assert!(!page_info.has_previous_page);
+
+    let after = CursorType::After(page_info.end_cursor.unwrap());
+    let response = app
+        .query
+        .categories(sorted(name, asc, Index::First(3), Some(after)).into_request())
+        .await?
+        .into_inner();
+    assert_eq!(names(&response), ["Electronics", "Gaming Laptops", "Jeans"]);
+
+    let page_info = response.page_info.unwrap();
+    assert!(page_info.has_next_page);
+    assert!(page_info.has_previous_page);
+
+    // last pages walk back from the end of the order
+    let desc = SortDirection::Desc;
+    let response = app
+        .query
+        .categories(sorted(name, desc, Index::Last(2), None).into_request())
+        .await?
+        .into_inner();
+    assert_eq!(names(&response), ["4K TVs", "Books"]);
+
+    let page_info = response.page_info.unwrap();
+    assert!(page_info.has_previous_page);
+    assert!(!page_info.has_next_page);
+
+    let before = CursorType::Before(page_info.end_cursor.unwrap());
+    let response = app
+        .query
+        .categories(sorted(name, desc, Index::Last(1), Some(before)).into_request())
+        .await?
+        .into_inner();
+    assert_eq!(names(&response), ["Clothing"]);
+    assert!(response.page_info.unwrap().has_next_page);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn categories_by_updated_at(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let request = UpdateCategoryRequest {
+        ap_id: "http://localhost/category/subcat6".into(),
+        category: Some(Category {
+            name: "Footwear".into(),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: None,
+    };
+    app.mutate.update(request.into_request()).await?;
+
+    let request = sorted(
+        CategorySortKey::UpdatedAt,
+        SortDirection::Desc,
+        Index::First(1),
+        None,
+    );
+    let response = app
+        .query
+        .categories(request.into_request())
+        .await?
+        .into_inner();
+    assert_eq!(names(&response), ["Footwear"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn sub_categories_by_name(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let request = GetSubCategoriesRequest {
+        id: Some("http://localhost/category/cat1".into()),
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(5)),
+        }),
+        sort: Some(CategorySort {
+            key: CategorySortKey::Name.into(),
+            direction: SortDirection::Desc.into(),
+        }),
+        ..Default::default()
+    };
+    let response = app
+        .query
+        .sub_categories(request.into_request())
+        .await?
+        .into_inner();
+    assert_eq!(
+        names(&response),
+        ["Televisions", "Mobile Phones", "Laptops"]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn cursor_must_match_sort(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let (name, asc) = (CategorySortKey::Name, SortDirection::Asc);
+
+    let response = app
+        .query
+        .categories(sorted(name, asc, Index::First(2), None).into_request())
+        .await?
+        .into_inner();
+    let cursor = response.page_info.unwrap().end_cursor.unwrap();
+
+    for (key, direction) in [
+        (name, SortDirection::Desc),
+        (CategorySortKey::CreatedAt, asc),
+    ] {
+        let after = CursorType::After(cursor.clone());
+        let status = app
+            .query
+            .categories(sorted(key, direction, Index::First(2), Some(after)).into_request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    let mut request = sorted(name, asc, Index::First(2), None);
+    request.sort = Some(CategorySort {
+        key: 9,
+        direction: 0,
+    });
+    let status = app
+        .query
+        .categories(request.into_request())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+
+    Ok(())
+}
@@ -17,10 +17,12 @@ async fn sub_no_parent_last(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: Some(Index::Last(2)),
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -42,10 +44,12 @@ async fn sub_no_parent_first(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: Some(Index::First(2)),
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -68,10 +72,12 @@ async fn sub_no_parent_first_lots(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: Some(Index::First(500)),
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -95,10 +101,12 @@ async fn sub_no_parent_last_exact(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: Some(Index::Last(2)),
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -121,10 +129,12 @@ async fn sub_cursor_last(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: Some(Index::Last(2)),
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -142,12 +152,14 @@ async fn sub_cursor_last(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: Some(CursorValue {
                 cursor_type: Some(CursorType::Before(cursor)),
             }),
             index: Some(cursor::Index::Last(1)),
         }),
+        ..Default::default()
     };
 
     let response = app.query.sub_categories(getter).await?.into_inner();
@@ -164,10 +176,12 @@ async fn sub_no_index(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: None,
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -185,10 +199,12 @@ async fn sub_cursor_first(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: None,
             index: Some(Index::First(2)),
         }),
+        ..Default::default()
     }
     .into_request();
 
@@ -206,12 +222,14 @@ async fn sub_cursor_first(pool: PgPool) -> Result<()> {
 
     let getter = GetSubCategoriesRequest {
         id: None,
+        include_roots: false,
         pagination: Some(Cursor {
             cursor_value: Some(CursorValue {
                 cursor_type: Some(CursorType::After(cursor)),
             }),
             index: Some(cursor::Index::First(1)),
         }),
+        ..Default::default()
     };
 
     let response = app.query.sub_categories(getter).await?.into_inner();
@@ -222,3 +240,99 @@ async fn sub_cursor_first(pool: PgPool) -> Result<()> {
 
     Ok(())
 }
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn sub_parent_first(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetSubCategoriesRequest {
+        id: Some("http://localhost/category/cat1".to_string()),
+        include_roots: false,
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(500)),
+        }),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.sub_categories(getter).await?.into_inner();
+
+    let ids: Vec<_> = response
+        .edges
+        .iter()
+        .map(|edge| edge.node.as_ref().unwrap().id.as_str())
+        .collect();
+    assert_eq!(ids, ["4", "5", "6"]);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn sub_parent_cursor_first(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetSubCategoriesRequest {
+        id: Some("http://localhost/category/cat1".to_string()),
+        include_roots: false,
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(2)),
+        }),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.sub_categories(getter).await?.into_inner();
+    assert_eq!(response.edges.len(), 2);
+
+    let cursor = response.page_info.unwrap().end_cursor.unwrap();
+
+    let getter = GetSubCategoriesRequest {
+        id: Some("http://localhost/category/cat1".to_string()),
+        include_roots: false,
+        pagination: Some(Cursor {
+            cursor_value: Some(CursorValue {
+                cursor_type: Some(CursorType::After(cursor)),
+            }),
+            index: Some(cursor::Index::First(500)),
+        }),
+        ..Default::default()
+    };
+
+    let response = app.query.sub_categories(getter).await?.into_inner();
+    assert_eq!(response.edges.len(), 1);
+
+    let first = &response.edges[0];
+    assert_eq!(first.node.as_ref().unwrap().id, "6");
+    assert!(!response.page_info.unwrap().has_next_page);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn sub_parent_include_roots(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let getter = GetSubCategoriesRequest {
+        id: Some("http://localhost/category/cat1".to_string()),
+        include_roots: true,
+        pagination: Some(Cursor {
+            cursor_value: None,
+            index: Some(Index::First(500)),
+        }),
+        ..Default::default()
+    }
+    .into_request();
+
+    let response = app.query.sub_categories(getter).await?.into_inner();
+
+    let ids: Vec<_> = response
+        .edges
+        .iter()
+        .map(|edge| edge.node.as_ref().unwrap().id.as_str())
+        .collect();
+    assert_eq!(ids, ["1", "2", "3", "4", "5", "6"]);
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use categories_service::watch::WatchEvent;
+use sellershut_core::categories::{
+    Category, CategoryChange, ChangeKind, CreateCategoryRequest, DeleteCategoryRequest,
+    WatchCategoriesRequest, delete_category_request::Identifier,
+};
+use sqlx::PgPool;
+use tonic::{Code, IntoRequest, Streaming};
+
+use crate::helpers::{TestApp, rename};
+
+async fn next_change(stream: &mut Streaming<CategoryChange>) -> Result<CategoryChange> {
+    let change = tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await??
+        .expect("stream to be open");
+    Ok(change)
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn watch_streams_changes(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let ap_id = "http://localhost/category/new";
+
+    let mut stream = app
+        .query
+        .watch(WatchCategoriesRequest { ap_id: None }.into_request())
+        .await?
+        .into_inner();
+
+    let category_request = CreateCategoryRequest {
+        category: Some(Category {
+            name: "Something".into(),
+            ap_id: ap_id.into(),
+            local: true,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    app.mutate.create(category_request.into_request()).await?;
+
+    let change = next_change(&mut stream).await?;
+    assert_eq!(change.kind(), ChangeKind::Created);
+    assert_eq!(change.ap_id, ap_id);
+    assert_eq!(change.category.unwrap().name, "Something");
+
+    app.mutate
+        .update(rename(ap_id, "Something Else").into_request())
+        .await?;
+
+    let change = next_change(&mut stream).await?;
+    assert_eq!(change.kind(), ChangeKind::Updated);
+    assert_eq!(change.category.unwrap().name, "Something Else");
+
+    let category_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId(ap_id.into())),
+        expected_updated_at: None,
+    };
+
+    app.mutate.delete(category_request.into_request()).await?;
+
+    let change = next_change(&mut stream).await?;
+    assert_eq!(change.kind(), ChangeKind::Deleted);
+    assert_eq!(change.ap_id, ap_id);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn watch_subtree(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let mut stream = app
+        .query
+        .watch(
+            WatchCategoriesRequest {
+                ap_id: Some("http://localhost/category/cat1".into()),
+            }
+            .into_request(),
+        )
+        .await?
+        .into_inner();
+
+    // under cat2, so not streamed
+    app.mutate
+        .update(rename("http://localhost/category/item4", "Adidas T-Shirts").into_request())
+        .await?;
+
+    app.mutate
+        .update(rename("http://localhost/category/item1", "Phones").into_request())
+        .await?;
+
+    let change = next_change(&mut stream).await?;
+    assert_eq!(change.kind(), ChangeKind::Updated);
+    assert_eq!(change.ap_id, "http://localhost/category/item1");
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../.././fixtures", scripts("categories")))]
+async fn watch_ends_when_interrupted(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+
+    let mut stream = app
+        .query
+        .watch(WatchCategoriesRequest { ap_id: None }.into_request())
+        .await?
+        .into_inner();
+
+    // as the listener does after reconnecting
+    app.state.changes.send(WatchEvent::Interrupted)?;
+
+    let err = tokio::time::timeout(Duration::from_secs(5), stream.message())
+        .await?
+        .unwrap_err();
+    assert_eq!(err.code(), Code::DataLoss);
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use categories_service::seed::{self, RemovedCategories, SeedConfig, SeedSummary};
+use sellershut_core::categories::{DeleteCategoryRequest, delete_category_request::Identifier};
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+fn config(removed: RemovedCategories) -> SeedConfig {
+    SeedConfig {
+        file: "tests/fixtures/taxonomy.toml".into(),
+        removed,
+    }
+}
+
+#[sqlx::test]
+async fn seed_empty_database(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+    let config = config(RemovedCategories::Keep);
+
+    let summary = seed::reconcile(&app.state, &config).await?;
+    assert_eq!(
+        summary,
+        SeedSummary {
+            inserted: 4,
+            ..Default::default()
+        }
+    );
+
+    let sub_categories = sqlx::query_scalar!(
+        "select sub_categories from category where ap_id = 'http://localhost/category/cat1'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(
+        sub_categories,
+        [
+            "http://localhost/category/subcat1",
+            "http://localhost/category/wearables"
+        ]
+    );
+
+    let history = sqlx::query_scalar!("select count(*) from category_history")
+        .fetch_one(pool)
+        .await?;
+
+    // nothing changed, so nothing is written
+    let summary = seed::reconcile(&app.state, &config).await?;
+    assert_eq!(summary, SeedSummary::default());
+
+    let unchanged = sqlx::query_scalar!("select count(*) from category_history")
+        .fetch_one(pool)
+        .await?;
+    assert_eq!(history, unchanged);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn seed_keeps_undeclared(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    let summary = seed::reconcile(&app.state, &config(RemovedCategories::Keep)).await?;
+    assert_eq!(
+        summary,
+        SeedSummary {
+            inserted: 2,
+            updated: 1,
+            ..Default::default()
+        }
+    );
+
+    let cat1 = sqlx::query!(
+        "select name, sub_categories from category where ap_id = 'http://localhost/category/cat1'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(cat1.name, "Electronics & Gadgets");
+    assert_eq!(
+        cat1.sub_categories,
+        [
+            "http://localhost/category/subcat1",
+            "http://localhost/category/subcat2",
+            "http://localhost/category/subcat3",
+            "http://localhost/category/wearables"
+        ]
+    );
+
+    let live = sqlx::query_scalar!("select count(*) from category where deleted_at is null")
+        .fetch_one(pool)
+        .await?;
+    assert_eq!(live, Some(20));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn seed_deletes_undeclared(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    // categories the file never declared are left alone
+    let summary = seed::reconcile(&app.state, &config(RemovedCategories::Delete)).await?;
+    assert_eq!(summary.deleted, 0);
+
+    let config = SeedConfig {
+        file: "tests/fixtures/taxonomy-trimmed.toml".into(),
+        removed: RemovedCategories::Delete,
+    };
+    let summary = seed::reconcile(&app.state, &config).await?;
+    assert_eq!(summary.deleted, 2);
+
+    let deleted = sqlx::query_scalar!(
+        "select ap_id from category where deleted_at is not null order by ap_id"
+    )
+    .fetch_all(pool)
+    .await?;
+    assert_eq!(
+        deleted,
+        [
+            "http://localhost/category/garden",
+            "http://localhost/category/wearables"
+        ]
+    );
+
+    let live = sqlx::query_scalar!("select count(*) from category where deleted_at is null")
+        .fetch_one(pool)
+        .await?;
+    assert_eq!(live, Some(18));
+
+    let actor = sqlx::query_scalar!(
+        "
+        select actor from category_history
+        where category_ap_id = 'http://localhost/category/garden' and operation = 'delete'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(actor.as_deref(), Some("seed"));
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn seed_leaves_deleted(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let config = config(RemovedCategories::Keep);
+
+    seed::reconcile(&app.state, &config).await?;
+
+    let delete_request = DeleteCategoryRequest {
+        identifier: Some(Identifier::ApId("http://localhost/category/cat1".into())),
+        expected_updated_at: None,
+    };
+    app.mutate.delete(delete_request.into_request()).await?;
+
+    // cat1 stays deleted, along with subcat1 and wearables declared beneath it
+    let summary = seed::reconcile(&app.state, &config).await?;
+    assert_eq!(
+        summary,
+        SeedSummary {
+            skipped: 3,
+            ..Default::default()
+        }
+    );
+
+    let live = sqlx::query_scalar!(
+        "
+        select count(*) from category
+        where ap_id = any(array[
+            'http://localhost/category/cat1',
+            'http://localhost/category/subcat1',
+            'http://localhost/category/wearables'
+        ]) and deleted_at is null"
+    )
+    .fetch_one(&app.state.services.postgres)
+    .await?;
+    assert_eq!(live, Some(0));
+
+    Ok(())
+}
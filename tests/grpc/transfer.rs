@@ -0,0 +1,193 @@
+use anyhow::Result;
+use categories_service::transfer::{self, CategoryNode, Format};
+use sellershut_core::categories::{Category, CreateCategoryRequest};
+use sqlx::PgPool;
+use tonic::IntoRequest;
+
+use crate::helpers::TestApp;
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn export_json_tree(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+
+    let exported = transfer::export(&app.state.services.postgres, Format::Json).await?;
+    let tree: Vec<CategoryNode> = serde_json::from_str(&exported)?;
+
+    let roots: Vec<_> = tree.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(roots, ["Books", "Clothing", "Electronics"]);
+
+    let electronics: Vec<_> = tree[2]
+        .sub_categories
+        .iter()
+        .map(|node| node.name.as_str())
+        .collect();
+    assert_eq!(electronics, ["Mobile Phones", "Laptops", "Televisions"]);
+    assert_eq!(
+        tree[2].sub_categories[0].sub_categories[0].name,
+        "Smartphones"
+    );
+    assert!(!tree[0].sub_categories[0].local);
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn import_json_is_idempotent(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    let exported = transfer::export(pool, Format::Json).await?;
+
+    let imported = transfer::import(&app.state, Format::Json, &exported).await?;
+    assert_eq!(imported, 18);
+    assert_eq!(transfer::export(pool, Format::Json).await?, exported);
+
+    transfer::import(&app.state, Format::Json, &exported).await?;
+    assert_eq!(transfer::export(pool, Format::Json).await?, exported);
+
+    let actor = sqlx::query_scalar!(
+        "select actor from category_history where category_ap_id = 'http://localhost/category/cat1'"
+    )
+    .fetch_all(pool)
+    .await?;
+    assert_eq!(
+        actor,
+        [Some("import".to_string()), Some("import".to_string())]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn import_keeps_unlisted_sub_categories(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    let exported = transfer::export(pool, Format::Json).await?;
+
+    // created under a category the export lists no sub-categories for
+    let category = Category {
+        name: "Foldables".into(),
+        ap_id: "http://localhost/category/foldables".into(),
+        parent_id: Some("http://localhost/category/item1".into()),
+        local: true,
+        ..Default::default()
+    };
+    let request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+    app.mutate.create(request.into_request()).await?;
+
+    transfer::import(&app.state, Format::Json, &exported).await?;
+
+    let sub_categories = sqlx::query_scalar!(
+        "select sub_categories from category where ap_id = 'http://localhost/category/item1'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(sub_categories, ["http://localhost/category/foldables"]);
+
+    let parent_id = sqlx::query_scalar!(
+        "select parent_id from category where ap_id = 'http://localhost/category/foldables'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(
+        parent_id.as_deref(),
+        Some("http://localhost/category/item1")
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn import_csv_into_empty_taxonomy(pool: PgPool) -> Result<()> {
+    let mut app = TestApp::new(pool).await;
+    let pool = &app.state.services.postgres;
+
+    let category = Category {
+        name: "Tools, \"DIY\"".into(),
+        ap_id: "http://localhost/category/tools".into(),
+        parent_id: Some("http://localhost/category/cat1".into()),
+        local: true,
+        ..Default::default()
+    };
+    let request = CreateCategoryRequest {
+        category: Some(category),
+        ..Default::default()
+    };
+    app.mutate.create(request.into_request()).await?;
+
+    let exported = transfer::export(pool, Format::Csv).await?;
+    assert!(exported.starts_with("id,ap_id,name,parent,image_url,local\n"));
+    assert!(exported.contains(",\"Tools, \"\"DIY\"\"\",http://localhost/category/cat1,,true\n"));
+
+    sqlx::query!("delete from category").execute(pool).await?;
+
+    let imported = transfer::import(&app.state, Format::Csv, &exported).await?;
+    assert_eq!(imported, 19);
+
+    // the new rows get ids of their own
+    let without_ids = |csv: &str| -> Vec<String> {
+        csv.lines()
+            .map(|line| line.split_once(',').unwrap().1.to_string())
+            .collect()
+    };
+    let reexported = transfer::export(pool, Format::Csv).await?;
+    assert_eq!(without_ids(&reexported), without_ids(&exported));
+
+    let local = sqlx::query_scalar!(
+        "select local from category where ap_id = 'http://localhost/category/subcat7'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert!(!local);
+
+    let sub_categories = sqlx::query_scalar!(
+        "select sub_categories from category where ap_id = 'http://localhost/category/cat1'"
+    )
+    .fetch_one(pool)
+    .await?;
+    assert_eq!(
+        sub_categories,
+        [
+            "http://localhost/category/subcat1",
+            "http://localhost/category/subcat2",
+            "http://localhost/category/subcat3",
+            "http://localhost/category/tools"
+        ]
+    );
+
+    Ok(())
+}
+
+#[sqlx::test(fixtures(path = "../fixtures", scripts("categories")))]
+async fn import_applies_nothing_on_rejection(pool: PgPool) -> Result<()> {
+    let app = TestApp::new(pool).await;
+
+    let csv = "\
+id,ap_id,name,parent,image_url,local
+,http://localhost/category/garden,Garden,,,true
+,http://localhost/category/tools,Tools,http://localhost/category/missing,,true
+";
+
+    let err = transfer::import(&app.state, Format::Csv, csv)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("http://localhost/category/tools"));
+
+    let garden = sqlx::query_scalar!(
+        "select count(*) from category where ap_id = 'http://localhost/category/garden'"
+    )
+    .fetch_one(&app.state.services.postgres)
+    .await?;
+    assert_eq!(garden, Some(0));
+
+    let err = transfer::import(&app.state, Format::Csv, "ap_id,name\n")
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("csv header"));
+
+    Ok(())
+}
@@ -1,19 +1,23 @@
-use categories_service::{AppConfig, state::AppState};
-use sellershut_core::categories::{
-    mutate_categories_client::MutateCategoriesClient,
-    query_categories_client::QueryCategoriesClient,
+use categories_service::{AppConfig, outbox::MemorySink, state::AppState};
+use sellershut_core::{
+    categories::{
+        Category, UpdateCategoryRequest, mutate_categories_client::MutateCategoriesClient,
+        query_categories_client::QueryCategoriesClient,
+    },
+    google::protobuf::FieldMask,
 };
 use sellershut_services::{Services, tracing::TracingBuilder};
 use sqlx::PgPool;
 use tokio::sync::oneshot;
 use tonic::transport::Channel;
 
-use std::sync::Once;
+use std::sync::{Arc, Once};
 
 static TRACING: Once = Once::new();
 
 pub struct TestApp {
-    state: AppState,
+    pub state: AppState,
+    pub events: Arc<MemorySink>,
     pub query: QueryCategoriesClient<Channel>,
     pub mutate: MutateCategoriesClient<Channel>,
 }
@@ -32,9 +36,14 @@ impl TestApp {
 
         let app_config = AppConfig {
             max_query_results: 100,
+            deleted_retention_days: 30,
+            outbox_interval_ms: 20,
+            seed: None,
         };
 
-        let state = AppState::new(0, services, app_config);
+        let events = Arc::new(MemorySink::default());
+
+        let state = AppState::new(0, services, app_config, events.clone());
 
         dbg!(&state.addr.port());
 
@@ -50,8 +59,24 @@ impl TestApp {
 
         Self {
             state,
+            events,
             query: query_client,
             mutate: mutation_client,
         }
     }
 }
+
+/// An update that only renames a category
+pub fn rename(ap_id: &str, name: &str) -> UpdateCategoryRequest {
+    UpdateCategoryRequest {
+        ap_id: ap_id.into(),
+        category: Some(Category {
+            name: name.into(),
+            ..Default::default()
+        }),
+        update_mask: Some(FieldMask {
+            paths: vec!["name".into()],
+        }),
+        expected_updated_at: None,
+    }
+}